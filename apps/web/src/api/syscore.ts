@@ -9,6 +9,120 @@ export interface ExecuteResponse {
     output: string; // This is the Job ID
 }
 
+export interface TraceChunkInfo {
+    index: number;
+    first_step: number;
+    len: number;
+    start_time: number | null;
+    end_time: number | null;
+}
+
+export interface TraceSummary {
+    event_types: Record<string, number>;
+    max_stack_depth: number;
+    peak_memory: number;
+    line_steps: number;
+    max_recursion_depth: number;
+    lines: { filename: string | null; line: number; hits: number }[];
+    functions: {
+        function: string;
+        calls: number;
+        steps_inclusive: number;
+        steps_exclusive: number;
+        time_inclusive_ns: number;
+        time_exclusive_ns: number;
+        max_recursion_depth: number;
+    }[];
+    opcodes: Record<string, number>;
+    hardware: Record<string, { count: number; cost: number }>;
+    gc: { collections: number; total_pause_ns: number; max_pause_ns: number };
+    memory_timeline: { step: number; timestamp: number | null; memory: number }[];
+}
+
+export interface TraceManifest {
+    job_id: string;
+    chunk_size: number;
+    event_count: number;
+    start_time: number | null;
+    end_time: number | null;
+    chunks: TraceChunkInfo[];
+    summary: TraceSummary;
+    stored_at: string | null;
+    size_bytes: number;
+    summary_only: boolean;
+}
+
+export interface TraceEventsQuery {
+    from?: number;
+    to?: number;
+    start_time?: number;
+    end_time?: number;
+    limit?: number;
+}
+
+export interface TraceQuery {
+    type?: string;
+    event?: string;
+    function?: string;
+    within?: string;
+    file?: string;
+    line?: number;
+    min_depth?: number;
+    max_depth?: number;
+    start_time?: number;
+    end_time?: number;
+    changed?: string;
+    cursor?: string;
+    limit?: number;
+}
+
+export interface TraceQueryPage<T> {
+    job_id: string;
+    event_count: number;
+    matches: { step: number; event: T }[];
+    next_cursor: string | null;
+}
+
+export interface TraceEventsPage<T> {
+    job_id: string;
+    event_count: number;
+    from_step: number;
+    events: T[];
+    next_step: number | null;
+}
+
+export interface TraceLine {
+    filename: string | null;
+    line: number;
+}
+
+export interface TraceDiff {
+    a: { job_id: string; events: number; line_steps: number; duration_ns: number; peak_memory: number };
+    b: { job_id: string; events: number; line_steps: number; duration_ns: number; peak_memory: number };
+    lines_only_in_a: TraceLine[];
+    lines_only_in_b: TraceLine[];
+    line_hits: (TraceLine & { a: number; b: number; delta: number })[];
+    functions: {
+        function: string;
+        a_steps: number;
+        b_steps: number;
+        step_delta: number;
+        a_calls: number;
+        b_calls: number;
+        a_self_time_ns: number;
+        b_self_time_ns: number;
+        time_delta_ns: number;
+    }[];
+    peak_memory_delta: number;
+    first_divergence: {
+        line_step: number;
+        a_step: number | null;
+        b_step: number | null;
+        a: (TraceLine & { function: string }) | null;
+        b: (TraceLine & { function: string }) | null;
+    } | null;
+}
+
 export const sysCoreApi = {
     checkHealth: async (): Promise<boolean> => {
         try {
@@ -29,5 +143,43 @@ export const sysCoreApi = {
             return res.data.output; // Job ID
         }
         throw new Error(res.data.output);
+    },
+
+    // Reads the trace back from whichever trace store SysCore is configured with
+    getTrace: async <T = unknown>(jobId: string): Promise<T[]> => {
+        const res = await axios.get<T[]>(`${API_BASE}/traces/${encodeURIComponent(jobId)}`);
+        return res.data;
+    },
+
+    getTraceManifest: async (jobId: string): Promise<TraceManifest> => {
+        const res = await axios.get<TraceManifest>(`${API_BASE}/traces/${encodeURIComponent(jobId)}/manifest`);
+        return res.data;
+    },
+
+    getTraceSummary: async (jobId: string): Promise<TraceSummary> => {
+        const res = await axios.get<TraceSummary>(`${API_BASE}/traces/${encodeURIComponent(jobId)}/summary`);
+        return res.data;
+    },
+
+    // Filtered events with their step index, follow next_cursor for more
+    queryTrace: async <T = unknown>(jobId: string, query: TraceQuery): Promise<TraceQueryPage<T>> => {
+        const res = await axios.get<TraceQueryPage<T>>(`${API_BASE}/traces/${encodeURIComponent(jobId)}/query`, {
+            params: query
+        });
+        return res.data;
+    },
+
+    // Deltas are b - a
+    diffTraces: async (a: string, b: string): Promise<TraceDiff> => {
+        const res = await axios.get<TraceDiff>(`${API_BASE}/traces/diff`, { params: { a, b } });
+        return res.data;
+    },
+
+    // One page of a step or time range, follow next_step for the rest
+    getTraceEvents: async <T = unknown>(jobId: string, query: TraceEventsQuery): Promise<TraceEventsPage<T>> => {
+        const res = await axios.get<TraceEventsPage<T>>(`${API_BASE}/traces/${encodeURIComponent(jobId)}/events`, {
+            params: query
+        });
+        return res.data;
     }
 };
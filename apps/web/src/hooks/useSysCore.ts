@@ -1,6 +1,5 @@
 import { useState, useCallback } from 'react';
 import { sysCoreApi } from '../api/syscore';
-import { supabase } from '../lib/supabase';
 import { Persistence } from '../services/persistence';
 
 interface Metrics {
@@ -73,20 +72,17 @@ export const useSysCore = () => {
                 metrics: { cpu: 0, memory: 0 }
             }));
 
-            const { data, error } = await supabase
-                .from('execution_traces')
-                .select('trace_data')
-                .eq('job_id', jobId)
-                .single();
-
-            if (error) {
-                console.error("Supabase trace fetch error:", error);
-                setState(prev => ({ ...prev, logs: [`Error fetching trace: ${error.message}`], isExecuting: false }));
+            let events: TraceEvent[];
+            try {
+                events = await sysCoreApi.getTrace<TraceEvent>(jobId);
+            } catch (error) {
+                console.error("Trace fetch error:", error);
+                const message = error instanceof Error ? error.message : String(error);
+                setState(prev => ({ ...prev, logs: [`Error fetching trace: ${message}`], isExecuting: false }));
                 return;
             }
 
-            if (data && data.trace_data) {
-                const events = data.trace_data as TraceEvent[];
+            if (events.length > 0) {
                 const logs: string[] = [];
                 const history: TraceEvent[] = [];
 
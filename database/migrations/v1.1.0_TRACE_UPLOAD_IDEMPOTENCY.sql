@@ -0,0 +1,20 @@
+-- Migration: v1.1.0
+-- Makes trace uploads idempotent so syscore can safely retry spooled uploads
+
+-- Keep only the newest trace per job before adding the constraint; rows
+-- created at the same instant are told apart by id. This only affects legacy
+-- rows: since v1.2.0 syscore stores traces in trace_manifests/trace_chunks and
+-- no longer writes execution_traces.
+DELETE FROM public.execution_traces a
+USING public.execution_traces b
+WHERE a.job_id = b.job_id
+  AND (a.created_at, a.id) < (b.created_at, b.id);
+
+-- One trace per job, lets the backend upsert with on_conflict=job_id
+ALTER TABLE public.execution_traces
+  ADD CONSTRAINT execution_traces_job_id_key UNIQUE (job_id);
+
+-- Upserts need update rights on top of insert. Only the service role gets them,
+-- so the public anon key cannot overwrite stored traces.
+CREATE POLICY "Allow service update" ON public.execution_traces
+  FOR UPDATE USING (auth.role() = 'service_role');
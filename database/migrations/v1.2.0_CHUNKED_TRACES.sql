@@ -0,0 +1,41 @@
+-- Migration: v1.2.0
+-- Stores execution traces as ordered chunks plus a manifest row, so large
+-- traces fit within row limits and can be read back a range at a time.
+-- execution_traces is kept read-only for traces uploaded before this change.
+
+CREATE TABLE IF NOT EXISTS public.trace_manifests (
+  job_id text PRIMARY KEY,
+  chunk_size integer NOT NULL,
+  event_count integer NOT NULL,
+  start_time bigint,
+  end_time bigint,
+  chunks jsonb NOT NULL,
+  summary jsonb NOT NULL,
+  created_at timestamp with time zone DEFAULT timezone('utc'::text, now()) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS public.trace_chunks (
+  job_id text NOT NULL,
+  chunk_index integer NOT NULL,
+  events jsonb NOT NULL,
+  created_at timestamp with time zone DEFAULT timezone('utc'::text, now()) NOT NULL,
+  PRIMARY KEY (job_id, chunk_index)
+);
+
+ALTER TABLE public.trace_manifests ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.trace_chunks ENABLE ROW LEVEL SECURITY;
+
+-- Anyone may read traces; only the backend (service role) writes them
+CREATE POLICY "Allow public read access" ON public.trace_manifests
+  FOR SELECT USING (true);
+CREATE POLICY "Allow service insert" ON public.trace_manifests
+  FOR INSERT WITH CHECK (auth.role() = 'service_role');
+CREATE POLICY "Allow service update" ON public.trace_manifests
+  FOR UPDATE USING (auth.role() = 'service_role');
+
+CREATE POLICY "Allow public read access" ON public.trace_chunks
+  FOR SELECT USING (true);
+CREATE POLICY "Allow service insert" ON public.trace_chunks
+  FOR INSERT WITH CHECK (auth.role() = 'service_role');
+CREATE POLICY "Allow service update" ON public.trace_chunks
+  FOR UPDATE USING (auth.role() = 'service_role');
@@ -0,0 +1,20 @@
+-- Migration: v1.3.0
+-- Columns and permissions syscore needs to enforce trace retention and
+-- to delete traces on request.
+
+ALTER TABLE public.trace_manifests
+  ADD COLUMN IF NOT EXISTS stored_at timestamp with time zone,
+  ADD COLUMN IF NOT EXISTS size_bytes bigint NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS summary_only boolean NOT NULL DEFAULT false;
+
+UPDATE public.trace_manifests SET stored_at = created_at WHERE stored_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS trace_manifests_stored_at_idx ON public.trace_manifests (stored_at);
+
+-- Deletes are limited to the service role, which syscore should use when retention is on
+CREATE POLICY "Allow service delete" ON public.trace_manifests
+  FOR DELETE USING (auth.role() = 'service_role');
+CREATE POLICY "Allow service delete" ON public.trace_chunks
+  FOR DELETE USING (auth.role() = 'service_role');
+CREATE POLICY "Allow service delete" ON public.execution_traces
+  FOR DELETE USING (auth.role() = 'service_role');
@@ -4,15 +4,229 @@ import subprocess
 import json
 import time
 import os
+import codecs
+import threading
+
+EVENT_PREFIX = "__SYSCORE_EVENT__"
+# Debugger requests arrive on stdin as lines with this prefix and are passed
+# to the gdb script through a FIFO, the rest goes to the program
+CMD_PREFIX = "__SYSCORE_CMD__"
+CMD_FIFO = "cmd.fifo"
+
+# Debug mode: instead of stepping through everything, pause at main and let
+# the client drive through requests read from CMD_FIFO
+DEBUG_GDB_SCRIPT = """
+import gdb
+import json
+import time
+import sys
+
+RESUME = {"continue": "continue", "next": "next", "stepIn": "step", "stepOut": "finish"}
+last_stop = {"reason": "step"}
+
+def emit(event):
+    event["timestamp"] = time.time_ns()
+    print(f"__SYSCORE_EVENT__{json.dumps(event)}")
+    sys.stdout.flush()
+
+def on_stop(event):
+    if isinstance(event, gdb.BreakpointEvent):
+        last_stop["reason"] = "breakpoint"
+    elif isinstance(event, gdb.SignalEvent):
+        last_stop["reason"] = "signal"
+
+def alive():
+    return gdb.selected_inferior().pid != 0
+
+def user_frames():
+    frames = []
+    f = gdb.newest_frame()
+    while f:
+        sal = f.find_sal()
+        if sal.symtab and sal.symtab.filename == "main.cpp":
+            frames.append(f)
+        f = f.older()
+    return frames
+
+def frame_at(frames, arguments):
+    frame_id = int(arguments.get("frameId", 0))
+    if not 0 <= frame_id < len(frames):
+        raise ValueError(f"No frame {frame_id}")
+    return frames[frame_id]
+
+def frame_variables(frame):
+    variables = []
+    seen = set()
+    block = frame.block()
+    while block:
+        for symbol in block:
+            if (symbol.is_argument or symbol.is_variable) and symbol.name not in seen:
+                seen.add(symbol.name)
+                try:
+                    value = str(symbol.value(frame))
+                except gdb.error as e:
+                    value = f"<{e}>"
+                variables.append({"name": symbol.name, "value": value, "type": str(symbol.type)})
+        if block.function:
+            break
+        block = block.superblock
+    return variables
+
+def set_breakpoints(breakpoints, requested):
+    for bp in breakpoints:
+        bp.delete()
+    breakpoints.clear()
+    result = []
+    for entry in requested:
+        line = int(entry["line"])
+        try:
+            bp = gdb.Breakpoint(f"main.cpp:{line}")
+        except gdb.error as e:
+            result.append({"line": line, "verified": False, "message": str(e)})
+            continue
+        try:
+            if entry.get("condition"):
+                bp.condition = entry["condition"]
+        except gdb.error as e:
+            bp.delete()
+            result.append({"line": line, "verified": False, "message": f"Invalid condition: {e}"})
+            continue
+        breakpoints.append(bp)
+        result.append({"line": line, "verified": not bp.pending})
+    return result
+
+def handle(command, arguments, frames, breakpoints):
+    if command == "setBreakpoints":
+        return {"breakpoints": set_breakpoints(breakpoints, arguments.get("breakpoints", []))}
+    if command == "stackTrace":
+        return {"stackFrames": [
+            {"id": i, "name": f.name(), "line": f.find_sal().line, "filename": "main.cpp"}
+            for i, f in enumerate(frames)
+        ]}
+    if command == "variables":
+        return {"variables": frame_variables(frame_at(frames, arguments))}
+    if command == "evaluate":
+        frame_at(frames, arguments).select()
+        try:
+            value = gdb.parse_and_eval(arguments["expression"])
+            return {"name": arguments["expression"], "value": str(value), "type": str(value.type)}
+        finally:
+            gdb.newest_frame().select()
+    if command == "stepOut" and len(frames) < 2:
+        raise ValueError("Already in the outermost frame")
+    if command not in RESUME:
+        raise ValueError(f"Unknown command: {command}")
+
+def respond(request, success, body=None, message=None):
+    event = {
+        "type": "Debug",
+        "event": "response",
+        "request_seq": request.get("seq", 0),
+        "command": request.get("command"),
+        "success": success,
+    }
+    if body is not None: event["body"] = body
+    if message is not None: event["message"] = message
+    emit(event)
+
+def debug():
+    gdb.execute("set pagination off")
+    gdb.execute("set confirm off")
+    # Stepping never enters the standard library
+    gdb.execute("skip -rfu ^std::", to_string=True)
+    gdb.execute("skip -rfu ^__gnu_cxx::", to_string=True)
+    gdb.events.stop.connect(on_stop)
+    commands = open("cmd.fifo")
+
+    gdb.execute("tbreak main", to_string=True)
+    try:
+        gdb.execute("run", to_string=True)
+    except gdb.error as e:
+        emit({"type": "Error", "content": f"Run failed: {e}"})
+        return
+
+    reason = "entry"
+    breakpoints = []
+    while alive():
+        frames = user_frames()
+        frame = gdb.selected_frame()
+        emit({
+            "type": "Debug",
+            "event": "stopped",
+            "reason": reason,
+            "line": frame.find_sal().line,
+            "function": frame.name(),
+            "filename": "main.cpp",
+            "stack_depth": len(frames),
+        })
+        while True:
+            line = commands.readline()
+            if not line:
+                # Nobody is left to drive us, run to the end
+                set_breakpoints(breakpoints, [])
+                command = "continue"
+                break
+            try:
+                request = json.loads(line)
+            except ValueError:
+                continue
+            command = request.get("command")
+            try:
+                body = handle(command, request.get("arguments") or {}, frames, breakpoints)
+            except Exception as e:
+                respond(request, False, message=str(e))
+                continue
+            respond(request, True, body=body)
+            if command in RESUME:
+                break
+        last_stop["reason"] = "step"
+        try:
+            gdb.execute(RESUME[command], to_string=True)
+        except gdb.error:
+            pass # The program exited
+        reason = last_stop["reason"]
+
+    emit({"type": "Debug", "event": "terminated"})
+
+debug()
+gdb.execute("quit")
+"""
+
+def route_stdin(process):
+    # Splits our stdin between the gdb script (requests) and the program
+    commands = open(CMD_FIFO, "wb")
+    for line in open(0, "rb", closefd=False):
+        target, data = (commands, line[len(CMD_PREFIX):]) if line.startswith(CMD_PREFIX.encode()) else (process.stdin, line)
+        try:
+            target.write(data)
+            target.flush()
+        except OSError:
+            pass # gdb or the program is gone
+    for stream in (commands, process.stdin):
+        try:
+            stream.close()
+        except OSError:
+            pass
+
+# Linked into every program so output reaches us (and interactive users) as it
+# is written, instead of when a pipe-sized buffer fills up
+UNBUFFERED_CPP = """
+#include <cstdio>
+__attribute__((constructor)) static void syscore_unbuffered() {
+    setvbuf(stdout, nullptr, _IONBF, 0);
+}
+"""
 
 def run_cpp_code(code_string):
     # 1. Write Code
     with open("main.cpp", "w") as f:
         f.write(code_string)
+    with open("syscore_unbuffered.cpp", "w") as f:
+        f.write(UNBUFFERED_CPP)
 
     # 2. Compile
     compile_proc = subprocess.run(
-        ["g++", "-g", "-O0", "main.cpp", "-o", "app"],
+        ["g++", "-g", "-O0", "main.cpp", "syscore_unbuffered.cpp", "-o", "app"],
         capture_output=True,
         text=True
     )
@@ -140,31 +354,48 @@ def trace():
 trace()
 gdb.execute("quit")
 """
+    debug = os.environ.get("SYSCORE_DEBUG") == "1"
     with open("trace.py", "w") as f:
-        f.write(gdb_script)
+        f.write(DEBUG_GDB_SCRIPT if debug else gdb_script)
+    if debug:
+        os.mkfifo(CMD_FIFO)
 
     # 4. Run GDB
+    # stdin is inherited, so input sent to the container reaches the program.
+    # In debug mode it is split by route_stdin instead.
     process = subprocess.Popen(
         ["gdb", "--batch", "-x", "trace.py", "./app"],
+        stdin=subprocess.PIPE if debug else None,
         stdout=subprocess.PIPE,
-        stderr=subprocess.STDOUT, 
-        text=True,
-        bufsize=1
+        stderr=subprocess.STDOUT,
     )
+    if debug:
+        threading.Thread(target=route_stdin, args=(process,), daemon=True).start()
 
     print("DEBUG: GDB-Python Runner Started", flush=True)
-    
-    # Proxy output
+
+    # Proxy output. Complete lines (including our __SYSCORE_EVENT__ lines) pass
+    # through; a trailing partial line such as an input prompt is sent on as a
+    # Stdout event right away, unless it may be the start of an event line.
+    decoder = codecs.getincrementaldecoder("utf-8")(errors="replace")
+    pending = ""
     while True:
-        line = process.stdout.readline()
-        if not line and process.poll() is not None:
+        chunk = os.read(process.stdout.fileno(), 4096)
+        if not chunk:
             break
-        if not line:
-            continue
-        
-        # Directly print GDB output (it contains our __SYSCORE_EVENT__ lines)
-        sys.stdout.write(line)
+        pending += decoder.decode(chunk)
+        *lines, pending = pending.split("\n")
+        for line in lines:
+            sys.stdout.write(line + "\n")
+        if pending and not (pending.startswith(EVENT_PREFIX) or EVENT_PREFIX.startswith(pending)):
+            event = {"type": "Stdout", "content": pending, "timestamp": time.time_ns()}
+            sys.stdout.write(f"{EVENT_PREFIX}{json.dumps(event)}\n")
+            pending = ""
+        sys.stdout.flush()
+    if pending:
+        sys.stdout.write(pending + "\n")
         sys.stdout.flush()
+    process.wait()
 
 if __name__ == "__main__":
     try:
@@ -7,6 +7,8 @@ import threading
 import gc
 import dis
 import time
+import bdb
+import queue
 
 # Initialize tracemalloc
 tracemalloc.start()
@@ -165,6 +167,182 @@ def gc_callback(phase, info):
     sys.__stdout__.write(f"__SYSCORE_EVENT__{json.dumps(event_data)}\n")
     sys.__stdout__.flush()
 
+# Debugger requests arrive on stdin as lines with this prefix
+CMD_PREFIX = "__SYSCORE_CMD__"
+USER_FILE = "user_code.py"
+
+def emit_event(event):
+    event.setdefault("timestamp", time.time_ns())
+    sys.__stdout__.write(f"__SYSCORE_EVENT__{json.dumps(event)}\n")
+    sys.__stdout__.flush()
+
+def describe_value(name, value):
+    try:
+        s_val = repr(value)
+    except Exception:
+        s_val = "<error>"
+    if len(s_val) > 200: s_val = s_val[:200] + "..."
+    return {"name": name, "value": s_val, "type": type(value).__name__}
+
+def split_stdin(commands):
+    # Debugger requests go to `commands`, everything else to a pipe that
+    # becomes the program's stdin. None in `commands` means stdin closed.
+    read_fd, write_fd = os.pipe()
+
+    def pump():
+        program_input = os.fdopen(write_fd, "wb")
+        # Our own reader, sys.stdin's lock must stay free for interpreter shutdown
+        for line in open(0, "rb", closefd=False):
+            if line.startswith(CMD_PREFIX.encode()):
+                try:
+                    commands.put(json.loads(line[len(CMD_PREFIX):]))
+                except ValueError:
+                    pass
+                continue
+            try:
+                program_input.write(line)
+                program_input.flush()
+            except OSError:
+                pass # The program closed its stdin
+        try:
+            program_input.close()
+        except OSError:
+            pass
+        commands.put(None)
+
+    threading.Thread(target=pump, daemon=True).start()
+    sys.stdin = io.TextIOWrapper(os.fdopen(read_fd, "rb"))
+
+class DebugController(bdb.Bdb):
+    # Pauses in user code where the client asks to and answers its requests
+    # while paused. Requests sent while the program runs wait for the next stop.
+    RESUME = {"continue", "next", "stepIn", "stepOut"}
+
+    def __init__(self, commands):
+        super().__init__()
+        self.commands = commands
+        self.user_file = self.canonic(USER_FILE)
+        self.frames = []
+        self.reason = "entry"
+        self.hit_breakpoint = False
+
+    def is_user_frame(self, frame):
+        return self.canonic(frame.f_code.co_filename) == self.user_file
+
+    def stop_here(self, frame):
+        # Never pause inside the standard library
+        return self.is_user_frame(frame) and super().stop_here(frame)
+
+    def break_here(self, frame):
+        hit = super().break_here(frame)
+        self.hit_breakpoint = self.hit_breakpoint or hit
+        return hit
+
+    def user_line(self, frame):
+        reason = "breakpoint" if self.hit_breakpoint else self.reason
+        self.hit_breakpoint = False
+        self.frames = []
+        f = frame
+        while f:
+            if self.is_user_frame(f):
+                self.frames.append(f)
+            f = f.f_back
+        emit_event({
+            "type": "Debug",
+            "event": "stopped",
+            "reason": reason,
+            "line": frame.f_lineno,
+            "function": frame.f_code.co_name,
+            "filename": USER_FILE,
+            "stack_depth": len(self.frames),
+        })
+        self.wait(frame)
+
+    def wait(self, frame):
+        while True:
+            request = self.commands.get()
+            if request is None:
+                # Nobody is left to drive us, run to the end
+                self.clear_all_breaks()
+                self.set_continue()
+                return
+            command = request.get("command")
+            try:
+                body = self.handle(frame, command, request.get("arguments") or {})
+            except Exception as e:
+                self.respond(request, False, message=str(e))
+                continue
+            self.respond(request, True, body=body)
+            if command in self.RESUME:
+                self.reason = "step"
+                return
+
+    def respond(self, request, success, body=None, message=None):
+        event = {
+            "type": "Debug",
+            "event": "response",
+            "request_seq": request.get("seq", 0),
+            "command": request.get("command"),
+            "success": success,
+        }
+        if body is not None: event["body"] = body
+        if message is not None: event["message"] = message
+        emit_event(event)
+
+    def frame(self, arguments):
+        frame_id = int(arguments.get("frameId", 0))
+        if not 0 <= frame_id < len(self.frames):
+            raise ValueError(f"No frame {frame_id}")
+        return self.frames[frame_id]
+
+    def handle(self, frame, command, arguments):
+        if command == "continue":
+            self.set_continue()
+        elif command == "next":
+            self.set_next(frame)
+        elif command == "stepIn":
+            self.set_step()
+        elif command == "stepOut":
+            self.set_return(frame)
+        elif command == "setBreakpoints":
+            return {"breakpoints": self.set_breakpoints(arguments.get("breakpoints", []))}
+        elif command == "stackTrace":
+            return {"stackFrames": [
+                {"id": i, "name": f.f_code.co_name, "line": f.f_lineno, "filename": USER_FILE}
+                for i, f in enumerate(self.frames)
+            ]}
+        elif command == "variables":
+            return {"variables": [describe_value(k, v) for k, v in self.frame(arguments).f_locals.items()]}
+        elif command == "evaluate":
+            f = self.frame(arguments)
+            value = eval(arguments["expression"], f.f_globals, f.f_locals)
+            return describe_value(arguments["expression"], value)
+        else:
+            raise ValueError(f"Unknown command: {command}")
+
+    def set_breakpoints(self, breakpoints):
+        self.clear_all_file_breaks(self.user_file)
+        result = []
+        for bp in breakpoints:
+            line = int(bp["line"])
+            condition = bp.get("condition") or None
+            error = None
+            try:
+                if condition: compile(condition, "<condition>", "eval")
+            except SyntaxError as e:
+                error = f"Invalid condition: {e.msg}"
+            error = error or self.set_break(self.user_file, line, cond=condition)
+            result.append({"line": line, "verified": error is None, **({"message": error} if error else {})})
+        return result
+
+def debug_user_code(code):
+    commands = queue.Queue()
+    split_stdin(commands)
+    try:
+        DebugController(commands).run(code, {'__name__': '__main__'})
+    finally:
+        emit_event({"type": "Debug", "event": "terminated"})
+
 def run_user_code(code_string):
     # Write to file
     with open("user_code.py", "w") as f:
@@ -194,8 +372,12 @@ def run_user_code(code_string):
     gc.callbacks.append(gc_callback)
     
     try:
-        sys.settrace(runner.trace_calls)
-        exec(compile(code_string, "user_code.py", "exec"), {'__name__': '__main__'})
+        code = compile(code_string, USER_FILE, "exec")
+        if os.environ.get("SYSCORE_DEBUG") == "1":
+            debug_user_code(code)
+        else:
+            sys.settrace(runner.trace_calls)
+            exec(code, {'__name__': '__main__'})
     except Exception as e:
         err_event = {"type": "Error", "content": str(e), "timestamp": time.time_ns()}
         sys.__stdout__.write(f"__SYSCORE_EVENT__{json.dumps(err_event)}\n")
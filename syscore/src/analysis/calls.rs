@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Which of the runner clocks to lay events out on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Clock {
+    /// `timestamp`, wall time in ns
+    #[default]
+    Wall,
+    /// `process_time`, CPU time of the traced process in ns
+    Process,
+}
+
+impl Clock {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Clock::Wall => "wall",
+            Clock::Process => "process",
+        }
+    }
+
+    pub fn read(self, event: &Value) -> Option<u64> {
+        let field = match self {
+            Clock::Wall => "timestamp",
+            Clock::Process => "process_time",
+        };
+        event.get(field).and_then(|t| t.as_u64())
+    }
+}
+
+/// One function call, rebuilt from the trace.
+#[derive(Debug, Clone)]
+pub struct CallSlice {
+    pub function: String,
+    pub filename: Option<String>,
+    /// 0 for the outermost traced frame
+    pub depth: usize,
+    /// Index of the calling slice
+    pub parent: Option<usize>,
+    pub start: u64,
+    pub end: u64,
+    pub first_step: usize,
+    /// Index of the event that ended the call (exclusive), or the trace length
+    pub end_step: usize,
+}
+
+/// Opening or closing of a slice, by index into `CallTree::slices`.
+#[derive(Debug, Clone, Copy)]
+pub enum CallEdge {
+    Open(usize),
+    Close(usize),
+}
+
+/// Calls rebuilt from a trace. `slices` are in the order they were entered,
+/// parents before children; `edges` is the properly nested open/close
+/// sequence, which tools wanting an event stream can replay as is.
+#[derive(Debug, Default)]
+pub struct CallTree {
+    pub slices: Vec<CallSlice>,
+    pub edges: Vec<CallEdge>,
+}
+
+impl CallTree {
+    /// Rebuilds the call tree from `Trace` events.
+    ///
+    /// The runners don't agree on call/return events (gdb only reports lines), so
+    /// frames are derived from `stack_depth` and `function`: the stack is
+    /// unwound or grown to match every event, and Python `return` events close
+    /// their frame right away. Depths are taken relative to the shallowest
+    /// traced frame. Events without a reading on `clock` are skipped.
+    pub fn rebuild(events: &[Value], clock: Clock) -> Self {
+        let base = events.iter()
+            .filter(|e| is_trace(e))
+            .filter_map(|e| e.get("stack_depth").and_then(|d| d.as_i64()))
+            .min()
+            .unwrap_or(0);
+
+        let mut tree = CallTree::default();
+        let mut stack: Vec<usize> = Vec::new();
+        let mut last_time = 0;
+
+        for (step, event) in events.iter().enumerate() {
+            if !is_trace(event) {
+                continue;
+            }
+            let (Some(time), Some(function), Some(depth)) = (
+                clock.read(event),
+                event.get("function").and_then(|f| f.as_str()),
+                event.get("stack_depth").and_then(|d| d.as_i64()),
+            ) else {
+                continue;
+            };
+            // Clocks can be coarse, never let a slice end before it starts
+            let time = time.max(last_time);
+            last_time = time;
+            let depth = (depth - base) as usize;
+
+            // Unwind frames deeper than this event, and the frame at its depth
+            // if the function changed (a sibling call)
+            while stack.len() > depth + 1
+                || (stack.len() == depth + 1 && tree.slices[stack[depth]].function != function)
+            {
+                tree.close(&mut stack, time, step);
+            }
+            // Grow to this depth. Frames we never saw the start of get the
+            // function name of the event that revealed them.
+            while stack.len() < depth + 1 {
+                let index = tree.slices.len();
+                tree.slices.push(CallSlice {
+                    function: function.to_string(),
+                    filename: event.get("filename").and_then(|f| f.as_str()).map(str::to_string),
+                    depth: stack.len(),
+                    parent: stack.last().copied(),
+                    start: time,
+                    end: time,
+                    first_step: step,
+                    end_step: step,
+                });
+                tree.edges.push(CallEdge::Open(index));
+                stack.push(index);
+            }
+
+            if event.get("event").and_then(|e| e.as_str()) == Some("return") {
+                tree.close(&mut stack, time, step + 1);
+            }
+        }
+
+        while !stack.is_empty() {
+            tree.close(&mut stack, last_time, events.len());
+        }
+        tree
+    }
+
+    /// Time in each slice minus the time in the slices it called, by slice index.
+    pub fn self_times(&self) -> Vec<u64> {
+        // Slices come parents first, so walking backwards settles children
+        // before their parent needs their time
+        let mut child_time = vec![0u64; self.slices.len()];
+        let mut self_time = vec![0u64; self.slices.len()];
+        for (i, slice) in self.slices.iter().enumerate().rev() {
+            let total = slice.end - slice.start;
+            self_time[i] = total.saturating_sub(child_time[i]);
+            if let Some(parent) = slice.parent {
+                child_time[parent] += total;
+            }
+        }
+        self_time
+    }
+
+    fn close(&mut self, stack: &mut Vec<usize>, end: u64, end_step: usize) {
+        if let Some(index) = stack.pop() {
+            self.slices[index].end = end;
+            self.slices[index].end_step = end_step;
+            self.edges.push(CallEdge::Close(index));
+        }
+    }
+}
+
+pub fn is_trace(event: &Value) -> bool {
+    event.get("type").and_then(|t| t.as_str()) == Some("Trace")
+}
+
+/// A `line` event, or a trace event from a runner that doesn't say (gdb).
+/// These are the steps a student sees when scrubbing through a trace.
+pub fn is_line_step(event: &Value) -> bool {
+    is_trace(event) && event.get("event").and_then(|e| e.as_str()).is_none_or(|e| e == "line")
+}
+
+/// A source line as the runners report it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineKey {
+    pub filename: Option<String>,
+    pub line: i64,
+}
+
+impl LineKey {
+    pub fn of(event: &Value) -> Option<Self> {
+        Some(Self {
+            filename: event.get("filename").and_then(|f| f.as_str()).map(str::to_string),
+            line: event.get("line").and_then(|l| l.as_i64())?,
+        })
+    }
+}
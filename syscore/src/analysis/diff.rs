@@ -0,0 +1,226 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use super::calls::{is_line_step, CallTree, Clock, LineKey};
+use super::summary::TraceSummary;
+
+#[derive(Serialize, Debug)]
+pub struct LineDelta {
+    #[serde(flatten)]
+    pub key: LineKey,
+    pub a: usize,
+    pub b: usize,
+    pub delta: i64,
+}
+
+/// Per function: line steps executed in it, number of calls and self time
+/// (time in the function minus time in the functions it called).
+#[derive(Serialize, Debug)]
+pub struct FunctionDelta {
+    pub function: String,
+    pub a_steps: usize,
+    pub b_steps: usize,
+    pub step_delta: i64,
+    pub a_calls: usize,
+    pub b_calls: usize,
+    pub a_self_time_ns: u64,
+    pub b_self_time_ns: u64,
+    pub time_delta_ns: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Location {
+    pub function: String,
+    #[serde(flatten)]
+    pub line: LineKey,
+}
+
+/// Where the two runs first took a different path. A side is `None` when its
+/// trace ended while the other one went on.
+#[derive(Serialize, Debug)]
+pub struct Divergence {
+    /// Index into the sequence of executed lines, the same for both runs
+    pub line_step: usize,
+    pub a_step: Option<usize>,
+    pub b_step: Option<usize>,
+    pub a: Option<Location>,
+    pub b: Option<Location>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TraceSide {
+    pub job_id: String,
+    pub events: usize,
+    pub line_steps: usize,
+    pub duration_ns: u64,
+    pub peak_memory: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TraceDiff {
+    pub a: TraceSide,
+    pub b: TraceSide,
+    pub lines_only_in_a: Vec<LineKey>,
+    pub lines_only_in_b: Vec<LineKey>,
+    /// Lines whose hit counts differ, biggest change first
+    pub line_hits: Vec<LineDelta>,
+    /// Functions whose steps or time differ, biggest step change first
+    pub functions: Vec<FunctionDelta>,
+    pub peak_memory_delta: i64,
+    pub first_divergence: Option<Divergence>,
+}
+
+/// What the diff needs from one trace.
+struct Profile {
+    side: TraceSide,
+    /// (event index, function, line) of every executed line, in order
+    path: Vec<(usize, String, LineKey)>,
+    hits: HashMap<LineKey, usize>,
+    steps: HashMap<String, usize>,
+    calls: HashMap<String, usize>,
+    self_time: HashMap<String, u64>,
+}
+
+impl Profile {
+    fn build(job_id: &str, events: &[Value], summary: Option<&TraceSummary>, clock: Clock) -> Self {
+        let mut path = Vec::new();
+        let mut hits: HashMap<LineKey, usize> = HashMap::new();
+        let mut steps: HashMap<String, usize> = HashMap::new();
+        let mut peak_memory = 0;
+
+        for (index, event) in events.iter().enumerate() {
+            let memory = event.get("memory_peak").or_else(|| event.get("memory_curr")).and_then(|m| m.as_u64());
+            peak_memory = peak_memory.max(memory.unwrap_or(0));
+
+            if !is_line_step(event) {
+                continue;
+            }
+            let Some(key) = LineKey::of(event) else { continue };
+            let function = event.get("function").and_then(|f| f.as_str()).unwrap_or("?").to_string();
+            *hits.entry(key.clone()).or_default() += 1;
+            *steps.entry(function.clone()).or_default() += 1;
+            path.push((index, function, key));
+        }
+
+        let mut calls: HashMap<String, usize> = HashMap::new();
+        let mut self_time: HashMap<String, u64> = HashMap::new();
+        match summary.filter(|s| clock == Clock::Wall && (!s.functions.is_empty() || path.is_empty())) {
+            // The stored summary already has them, on the wall clock
+            Some(summary) => {
+                for f in &summary.functions {
+                    calls.insert(f.function.clone(), f.calls);
+                    self_time.insert(f.function.clone(), f.time_exclusive_ns);
+                }
+            }
+            None => {
+                let tree = CallTree::rebuild(events, clock);
+                for (slice, time) in tree.slices.iter().zip(tree.self_times()) {
+                    *calls.entry(slice.function.clone()).or_default() += 1;
+                    *self_time.entry(slice.function.clone()).or_default() += time;
+                }
+            }
+        }
+
+        let times = events.iter().filter_map(|e| clock.read(e));
+        let duration_ns = match (times.clone().min(), times.max()) {
+            (Some(start), Some(end)) => end - start,
+            _ => 0,
+        };
+
+        Self {
+            side: TraceSide {
+                job_id: job_id.to_string(),
+                events: events.len(),
+                line_steps: path.len(),
+                duration_ns,
+                peak_memory,
+            },
+            path,
+            hits,
+            steps,
+            calls,
+            self_time,
+        }
+    }
+}
+
+/// One side of a diff: a trace's events, plus its stored summary when it has one.
+pub struct DiffInput<'a> {
+    pub job_id: &'a str,
+    pub events: &'a [Value],
+    pub summary: Option<&'a TraceSummary>,
+}
+
+pub fn diff(a: DiffInput, b: DiffInput, clock: Clock) -> TraceDiff {
+    let a = Profile::build(a.job_id, a.events, a.summary, clock);
+    let b = Profile::build(b.job_id, b.events, b.summary, clock);
+
+    let mut lines: BTreeMap<&LineKey, (usize, usize)> = BTreeMap::new();
+    for (key, &n) in &a.hits {
+        lines.entry(key).or_default().0 = n;
+    }
+    for (key, &n) in &b.hits {
+        lines.entry(key).or_default().1 = n;
+    }
+    let lines_only_in_a = lines.iter().filter(|(_, &(_, nb))| nb == 0).map(|(k, _)| (*k).clone()).collect();
+    let lines_only_in_b = lines.iter().filter(|(_, &(na, _))| na == 0).map(|(k, _)| (*k).clone()).collect();
+    let mut line_hits: Vec<LineDelta> = lines.iter()
+        .filter(|(_, &(na, nb))| na != nb)
+        .map(|(k, &(na, nb))| LineDelta { key: (*k).clone(), a: na, b: nb, delta: nb as i64 - na as i64 })
+        .collect();
+    line_hits.sort_by_key(|d| std::cmp::Reverse(d.delta.unsigned_abs()));
+
+    let mut names: Vec<&String> = a.steps.keys().chain(b.steps.keys())
+        .chain(a.calls.keys()).chain(b.calls.keys())
+        .collect();
+    names.sort();
+    names.dedup();
+    let mut functions: Vec<FunctionDelta> = names.into_iter().map(|name| {
+        let get = |m: &HashMap<String, usize>| m.get(name).copied().unwrap_or(0);
+        let time = |m: &HashMap<String, u64>| m.get(name).copied().unwrap_or(0);
+        FunctionDelta {
+            function: name.clone(),
+            a_steps: get(&a.steps),
+            b_steps: get(&b.steps),
+            step_delta: get(&b.steps) as i64 - get(&a.steps) as i64,
+            a_calls: get(&a.calls),
+            b_calls: get(&b.calls),
+            a_self_time_ns: time(&a.self_time),
+            b_self_time_ns: time(&b.self_time),
+            time_delta_ns: time(&b.self_time) as i64 - time(&a.self_time) as i64,
+        }
+    })
+    .filter(|f| f.step_delta != 0 || f.time_delta_ns != 0 || f.a_calls != f.b_calls)
+    .collect();
+    functions.sort_by_key(|f| std::cmp::Reverse(f.step_delta.unsigned_abs()));
+
+    TraceDiff {
+        peak_memory_delta: b.side.peak_memory as i64 - a.side.peak_memory as i64,
+        first_divergence: first_divergence(&a.path, &b.path),
+        lines_only_in_a,
+        lines_only_in_b,
+        line_hits,
+        functions,
+        a: a.side,
+        b: b.side,
+    }
+}
+
+/// First point where the sequences of executed (function, line) differ.
+fn first_divergence(a: &[(usize, String, LineKey)], b: &[(usize, String, LineKey)]) -> Option<Divergence> {
+    let same = |x: &(usize, String, LineKey), y: &(usize, String, LineKey)| x.1 == y.1 && x.2 == y.2;
+    let line_step = a.iter().zip(b).position(|(x, y)| !same(x, y))
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))?;
+
+    let location = |p: Option<&(usize, String, LineKey)>| p.map(|(_, function, line)| Location {
+        function: function.clone(),
+        line: line.clone(),
+    });
+    Some(Divergence {
+        line_step,
+        a_step: a.get(line_step).map(|p| p.0),
+        b_step: b.get(line_step).map(|p| p.0),
+        a: location(a.get(line_step)),
+        b: location(b.get(line_step)),
+    })
+}
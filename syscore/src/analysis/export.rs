@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use super::calls::{CallEdge, CallTree, Clock};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Chrome Trace Event JSON, opens in Perfetto and chrome://tracing
+    Chrome,
+    /// speedscope's evented profile format
+    Speedscope,
+}
+
+impl ExportFormat {
+    pub fn file_name(self, job_id: &str) -> String {
+        match self {
+            ExportFormat::Chrome => format!("{}.trace.json", job_id),
+            ExportFormat::Speedscope => format!("{}.speedscope.json", job_id),
+        }
+    }
+}
+
+pub fn export(format: ExportFormat, job_id: &str, events: &[Value], clock: Clock) -> Value {
+    match format {
+        ExportFormat::Chrome => chrome_trace(job_id, events, clock),
+        ExportFormat::Speedscope => speedscope(job_id, events, clock),
+    }
+}
+
+// Everything goes on one process/thread, the traced program is single threaded
+const PID: u32 = 1;
+const TID: u32 = 1;
+
+/// Chrome Trace Event format: calls become complete (`X`) slices, GC phases
+/// and program output become instant events and `memory_curr` a counter
+/// track. Timestamps are microseconds from the first event.
+pub fn chrome_trace(job_id: &str, events: &[Value], clock: Clock) -> Value {
+    let origin = events.iter().filter_map(|e| clock.read(e)).min().unwrap_or(0);
+    let micros = |t: u64| (t - origin) as f64 / 1000.0;
+
+    let mut out = vec![
+        json!({"name": "process_name", "ph": "M", "pid": PID, "args": {"name": format!("job {}", job_id)}}),
+        json!({"name": "thread_name", "ph": "M", "pid": PID, "tid": TID, "args": {"name": "main"}}),
+    ];
+
+    let tree = CallTree::rebuild(events, clock);
+    for slice in &tree.slices {
+        out.push(json!({
+            "name": slice.function,
+            "cat": "function",
+            "ph": "X",
+            "ts": micros(slice.start),
+            "dur": (slice.end - slice.start) as f64 / 1000.0,
+            "pid": PID,
+            "tid": TID,
+            "args": {"filename": slice.filename, "depth": slice.depth, "first_step": slice.first_step},
+        }));
+    }
+
+    let mut last_memory = None;
+    for (step, event) in events.iter().enumerate() {
+        let Some(time) = clock.read(event) else { continue };
+        let kind = event.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+        match kind {
+            "GC" => {
+                let phase = event.get("phase").and_then(|p| p.as_str()).unwrap_or("");
+                out.push(json!({
+                    "name": format!("GC {}", phase),
+                    "cat": "gc",
+                    "ph": "i",
+                    "s": "t",
+                    "ts": micros(time),
+                    "pid": PID,
+                    "tid": TID,
+                    "args": {"info": event.get("info"), "step": step},
+                }));
+            }
+            "Stdout" | "Stderr" | "Error" => {
+                out.push(json!({
+                    "name": kind,
+                    "cat": if kind == "Error" { "error" } else { "output" },
+                    "ph": "i",
+                    "s": "t",
+                    "ts": micros(time),
+                    "pid": PID,
+                    "tid": TID,
+                    "args": {"content": event.get("content"), "step": step},
+                }));
+            }
+            _ => {}
+        }
+
+        // Only emit the counter when it moves, traces repeat it on every line
+        if let Some(memory) = event.get("memory_curr").and_then(|m| m.as_u64()) {
+            if last_memory != Some(memory) {
+                last_memory = Some(memory);
+                out.push(json!({
+                    "name": "memory",
+                    "ph": "C",
+                    "ts": micros(time),
+                    "pid": PID,
+                    "args": {"memory_curr": memory},
+                }));
+            }
+        }
+    }
+
+    json!({
+        "traceEvents": out,
+        "displayTimeUnit": "ns",
+        "otherData": {"job_id": job_id, "clock": clock, "exporter": "syscore"},
+    })
+}
+
+/// speedscope evented profile built from the rebuilt call tree. speedscope
+/// has no notion of instant events or counters, so only calls are exported.
+pub fn speedscope(job_id: &str, events: &[Value], clock: Clock) -> Value {
+    let tree = CallTree::rebuild(events, clock);
+
+    let mut frames: Vec<Value> = Vec::new();
+    let mut frame_ids: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+    let slice_frames: Vec<usize> = tree.slices.iter().map(|slice| {
+        let key = (slice.function.as_str(), slice.filename.as_deref());
+        *frame_ids.entry(key).or_insert_with(|| {
+            frames.push(json!({"name": slice.function, "file": slice.filename}));
+            frames.len() - 1
+        })
+    }).collect();
+
+    let profile_events: Vec<Value> = tree.edges.iter().map(|edge| match *edge {
+        CallEdge::Open(i) => json!({"type": "O", "frame": slice_frames[i], "at": tree.slices[i].start}),
+        CallEdge::Close(i) => json!({"type": "C", "frame": slice_frames[i], "at": tree.slices[i].end}),
+    }).collect();
+
+    let start = tree.slices.iter().map(|s| s.start).min().unwrap_or(0);
+    let end = tree.slices.iter().map(|s| s.end).max().unwrap_or(0);
+
+    json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "name": format!("job {}", job_id),
+        "exporter": "syscore",
+        "activeProfileIndex": 0,
+        "shared": {"frames": frames},
+        "profiles": [{
+            "type": "evented",
+            "name": format!("job {} ({} clock)", job_id, clock.as_str()),
+            "unit": "nanoseconds",
+            "startValue": start,
+            "endValue": end,
+            "events": profile_events,
+        }],
+    })
+}
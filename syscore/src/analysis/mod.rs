@@ -0,0 +1,5 @@
+pub mod calls;
+pub mod diff;
+pub mod export;
+pub mod query;
+pub mod summary;
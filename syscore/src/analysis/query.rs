@@ -0,0 +1,139 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use super::calls::{is_trace, Clock};
+
+/// Filters for a trace query. Every filter that is set has to match; filters
+/// on trace fields (function, line, depth, ...) never match output or GC
+/// events.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct TraceFilter {
+    /// Comma separated event types (`Trace,Stdout`)
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    /// Comma separated trace event kinds (`line,call,return,opcode`)
+    pub event: Option<String>,
+    /// The function the event happened in
+    pub function: Option<String>,
+    /// Events while this function is anywhere on the stack, callees included
+    pub within: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<i64>,
+    pub min_depth: Option<i64>,
+    pub max_depth: Option<i64>,
+    /// Wall clock bounds in ns, inclusive
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Steps where this local got a new value (including its first one)
+    pub changed: Option<String>,
+}
+
+impl TraceFilter {
+    /// `changed` and `within` follow frames across events, so they need every
+    /// event in order and can't skip chunks.
+    pub fn is_stateful(&self) -> bool {
+        self.changed.is_some() || self.within.is_some()
+    }
+
+    /// Updates `state` with `event` and says whether the event matches.
+    pub fn matches(&self, state: &mut QueryState, event: &Value) -> bool {
+        let changed = self.changed.as_deref().map(|name| state.observe(event, name));
+        if changed == Some(false) {
+            return false;
+        }
+        if self.changed.is_none() && self.within.is_some() {
+            state.observe(event, "");
+        }
+
+        let str_field = |field: &str| event.get(field).and_then(|v| v.as_str());
+        let in_list = |list: &Option<String>, value: Option<&str>| match list {
+            Some(list) => value.is_some_and(|v| list.split(',').any(|item| item.trim() == v)),
+            None => true,
+        };
+        let depth = event.get("stack_depth").and_then(|d| d.as_i64());
+
+        in_list(&self.event_type, str_field("type"))
+            && in_list(&self.event, str_field("event").or(is_trace(event).then_some("line")))
+            && self.function.as_deref().is_none_or(|f| str_field("function") == Some(f))
+            && self.within.as_deref().is_none_or(|f| state.on_stack(f))
+            && self.file.as_deref().is_none_or(|f| str_field("filename") == Some(f))
+            && self.line.is_none_or(|l| event.get("line").and_then(|v| v.as_i64()) == Some(l))
+            && self.min_depth.is_none_or(|min| depth.is_some_and(|d| d >= min))
+            && self.max_depth.is_none_or(|max| depth.is_some_and(|d| d <= max))
+            && self.start_time.is_none_or(|start| Clock::Wall.read(event).is_some_and(|t| t >= start))
+            && self.end_time.is_none_or(|end| Clock::Wall.read(event).is_some_and(|t| t <= end))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    function: String,
+    /// Last seen value of the `changed` local in this frame
+    value: Option<Value>,
+}
+
+/// Frames seen so far, keyed by `stack_depth`. A resumed query rebuilds it by
+/// running the steps before the cursor through the filter again.
+#[derive(Debug, Clone, Default)]
+pub struct QueryState {
+    frames: BTreeMap<i64, Frame>,
+}
+
+impl QueryState {
+    /// Tracks the frame of a trace event and reports whether local `name`
+    /// has a different value than last time in this frame.
+    fn observe(&mut self, event: &Value, name: &str) -> bool {
+        if !is_trace(event) {
+            return false;
+        }
+        let (Some(depth), Some(function)) = (
+            event.get("stack_depth").and_then(|d| d.as_i64()),
+            event.get("function").and_then(|f| f.as_str()),
+        ) else {
+            return false;
+        };
+
+        // Frames deeper than this one have returned
+        self.frames.split_off(&(depth + 1));
+        let new_frame = event.get("event").and_then(|e| e.as_str()) == Some("call")
+            || self.frames.get(&depth).is_none_or(|f| f.function != function);
+        if new_frame {
+            self.frames.insert(depth, Frame { function: function.to_string(), value: None });
+        }
+
+        // Compare the rendered value only, address and size churn on their own
+        let value = event.get("locals")
+            .and_then(|l| l.get(name))
+            .map(|v| v.get("value").cloned().unwrap_or_else(|| v.clone()));
+        let frame = self.frames.get_mut(&depth).expect("frame inserted above");
+        if value.is_some() && value != frame.value {
+            frame.value = value;
+            true
+        } else {
+            frame.value = value;
+            false
+        }
+    }
+
+    fn on_stack(&self, function: &str) -> bool {
+        self.frames.values().any(|f| f.function == function)
+    }
+}
+
+/// Where a query stopped: the next step to scan. Only the step travels, so the
+/// cursor stays short however deep the stack was.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueryCursor {
+    pub step: usize,
+}
+
+impl QueryCursor {
+    pub fn encode(&self) -> String {
+        self.step.to_string()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let step = cursor.parse().map_err(|_| "Invalid cursor".to_string())?;
+        Ok(Self { step })
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use super::calls::{is_line_step, CallEdge, CallTree, Clock, LineKey};
+
+/// Points kept in the memory timeline, however long the trace is
+const MEMORY_TIMELINE_POINTS: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LineHits {
+    #[serde(flatten)]
+    pub line: LineKey,
+    pub hits: usize,
+}
+
+/// Steps count executed lines. Inclusive figures cover everything the
+/// function called too; recursive calls are only counted once, at their
+/// outermost frame.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FunctionStats {
+    pub function: String,
+    pub calls: usize,
+    pub steps_inclusive: usize,
+    pub steps_exclusive: usize,
+    pub time_inclusive_ns: u64,
+    pub time_exclusive_ns: u64,
+    /// Most frames of this function on the stack at once
+    pub max_recursion_depth: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HardwareTotals {
+    pub count: usize,
+    pub cost: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    pub total_pause_ns: u64,
+    pub max_pause_ns: u64,
+}
+
+/// Highest `memory_curr` within one slice of the trace.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryPoint {
+    pub step: usize,
+    pub timestamp: Option<u64>,
+    pub memory: u64,
+}
+
+/// Compact analytics kept with every stored trace, so views don't have to
+/// scan the events. Times use the wall clock (`timestamp`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TraceSummary {
+    /// Event count per `type` (Trace, Stdout, GC, ...)
+    pub event_types: BTreeMap<String, usize>,
+    pub max_stack_depth: i64,
+    pub peak_memory: u64,
+    #[serde(default)]
+    pub line_steps: usize,
+    #[serde(default)]
+    pub max_recursion_depth: usize,
+    #[serde(default)]
+    pub lines: Vec<LineHits>,
+    /// Most steps first
+    #[serde(default)]
+    pub functions: Vec<FunctionStats>,
+    #[serde(default)]
+    pub opcodes: BTreeMap<String, usize>,
+    /// Totals per `hardware.type` (MEM_READ, ALU, CONTROL, ...)
+    #[serde(default)]
+    pub hardware: BTreeMap<String, HardwareTotals>,
+    #[serde(default)]
+    pub gc: GcStats,
+    #[serde(default)]
+    pub memory_timeline: Vec<MemoryPoint>,
+}
+
+impl TraceSummary {
+    pub fn from_events(events: &[Value]) -> Self {
+        let mut summary = Self::default();
+        let mut hits: BTreeMap<LineKey, usize> = BTreeMap::new();
+        let mut functions: HashMap<String, FunctionStats> = HashMap::new();
+        // Line steps before each event, for inclusive step counts
+        let mut steps_before = Vec::with_capacity(events.len() + 1);
+        let mut gc_started: Option<u64> = None;
+        let mut memory: Vec<(usize, Option<u64>, u64)> = Vec::new();
+
+        for (step, event) in events.iter().enumerate() {
+            steps_before.push(summary.line_steps);
+            let kind = event.get("type").and_then(|t| t.as_str()).unwrap_or("Unknown");
+            *summary.event_types.entry(kind.to_string()).or_default() += 1;
+
+            if let Some(depth) = event.get("stack_depth").and_then(|d| d.as_i64()) {
+                summary.max_stack_depth = summary.max_stack_depth.max(depth);
+            }
+            let peak = event.get("memory_peak").or_else(|| event.get("memory_curr")).and_then(|m| m.as_u64());
+            if let Some(peak) = peak {
+                summary.peak_memory = summary.peak_memory.max(peak);
+            }
+            if let Some(curr) = event.get("memory_curr").and_then(|m| m.as_u64()) {
+                memory.push((step, Clock::Wall.read(event), curr));
+            }
+
+            if is_line_step(event) {
+                summary.line_steps += 1;
+                if let Some(key) = LineKey::of(event) {
+                    *hits.entry(key).or_default() += 1;
+                }
+                if let Some(function) = event.get("function").and_then(|f| f.as_str()) {
+                    stats(&mut functions, function).steps_exclusive += 1;
+                }
+            }
+
+            if let Some(opcode) = event.pointer("/bytecode/opcode").and_then(|o| o.as_str()) {
+                if !opcode.is_empty() {
+                    *summary.opcodes.entry(opcode.to_string()).or_default() += 1;
+                }
+            }
+            if let Some(kind) = event.pointer("/hardware/type").and_then(|t| t.as_str()) {
+                let totals = summary.hardware.entry(kind.to_string()).or_default();
+                totals.count += 1;
+                totals.cost += event.pointer("/hardware/cost").and_then(|c| c.as_u64()).unwrap_or(0);
+            }
+
+            if kind == "GC" {
+                let time = Clock::Wall.read(event);
+                match event.get("phase").and_then(|p| p.as_str()) {
+                    Some("start") => {
+                        summary.gc.collections += 1;
+                        gc_started = time;
+                    }
+                    Some("stop") => {
+                        if let (Some(start), Some(end)) = (gc_started.take(), time) {
+                            let pause = end.saturating_sub(start);
+                            summary.gc.total_pause_ns += pause;
+                            summary.gc.max_pause_ns = summary.gc.max_pause_ns.max(pause);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        steps_before.push(summary.line_steps);
+
+        // Calls, inclusive figures and recursion from the rebuilt call tree
+        let tree = CallTree::rebuild(events, Clock::Wall);
+        for (slice, time) in tree.slices.iter().zip(tree.self_times()) {
+            stats(&mut functions, &slice.function).time_exclusive_ns += time;
+        }
+        let mut active: HashMap<&str, usize> = HashMap::new();
+        for edge in &tree.edges {
+            match *edge {
+                CallEdge::Open(i) => {
+                    let slice = &tree.slices[i];
+                    let level = active.entry(&slice.function).or_default();
+                    *level += 1;
+                    let level = *level;
+                    let entry = stats(&mut functions, &slice.function);
+                    entry.calls += 1;
+                    entry.max_recursion_depth = entry.max_recursion_depth.max(level);
+                    summary.max_recursion_depth = summary.max_recursion_depth.max(level);
+                    if level == 1 {
+                        entry.steps_inclusive += steps_before[slice.end_step] - steps_before[slice.first_step];
+                        entry.time_inclusive_ns += slice.end - slice.start;
+                    }
+                }
+                CallEdge::Close(i) => {
+                    if let Some(level) = active.get_mut(tree.slices[i].function.as_str()) {
+                        *level -= 1;
+                    }
+                }
+            }
+        }
+
+        summary.lines = hits.into_iter().map(|(line, hits)| LineHits { line, hits }).collect();
+        summary.functions = functions.into_values().collect();
+        summary.functions.sort_by(|a, b| b.steps_inclusive.cmp(&a.steps_inclusive).then_with(|| a.function.cmp(&b.function)));
+        summary.memory_timeline = downsample(&memory);
+        summary
+    }
+}
+
+fn stats<'a>(functions: &'a mut HashMap<String, FunctionStats>, name: &str) -> &'a mut FunctionStats {
+    functions.entry(name.to_string()).or_insert_with(|| FunctionStats {
+        function: name.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Splits the samples into at most `MEMORY_TIMELINE_POINTS` buckets and keeps
+/// the highest sample of each, so peaks survive the downsampling.
+fn downsample(samples: &[(usize, Option<u64>, u64)]) -> Vec<MemoryPoint> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+    let bucket = samples.len().div_ceil(MEMORY_TIMELINE_POINTS);
+    samples.chunks(bucket)
+        .filter_map(|chunk| chunk.iter().max_by_key(|s| s.2))
+        .map(|&(step, timestamp, memory)| MemoryPoint { step, timestamp, memory })
+        .collect()
+}
@@ -0,0 +1,420 @@
+use super::{JobAssignment, RegisterRequest, RegisterResponse};
+use crate::docker::manager::{Language, RunOptions, StdinInput};
+use crate::jobs::events::JobEvents;
+use crate::jobs::registry::{JobRecord, JobRegistry, JobState};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use tokio::sync::{Mutex, Notify, oneshot};
+use uuid::Uuid;
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// A worker that has not been heard from for this long is considered dead
+const WORKER_TIMEOUT: Duration = Duration::from_secs(20);
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+/// How often a job is handed out again after its worker died before we give up
+const MAX_ATTEMPTS: u32 = 3;
+/// A job no worker picked up within this long is failed instead of waiting forever
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct WorkerInfo {
+    name: String,
+    capacity: usize,
+    languages: Vec<Language>,
+    last_seen: Instant,
+    running: HashSet<String>,
+}
+
+struct PendingJob {
+    assignment: JobAssignment,
+    attempts: u32,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    workers: HashMap<String, WorkerInfo>,
+    queue: VecDeque<PendingJob>,
+    // JobID -> (WorkerID, job) for re-queueing when a worker dies
+    assigned: HashMap<String, (String, PendingJob)>,
+    waiters: HashMap<String, oneshot::Sender<Result<String, String>>>,
+    // Assigned jobs to cancel; their worker is told on its next heartbeat
+    cancelling: HashSet<String>,
+    // Input for interactive jobs, until their worker fetches it
+    stdin: HashMap<String, VecDeque<StdinInput>>,
+}
+
+#[derive(Serialize)]
+pub struct WorkerStatus {
+    pub worker_id: String,
+    pub name: String,
+    pub capacity: usize,
+    pub running: usize,
+    pub languages: Vec<Language>,
+    pub last_seen_secs: u64,
+}
+
+/// Keeps track of registered workers and hands queued jobs to them.
+///
+/// Workers pull jobs (long-polling), so a worker only receives work while it has
+/// free slots; that keeps load spread by capacity without the coordinator having
+/// to push. Jobs of workers that miss their heartbeats go back to the queue.
+#[derive(Clone)]
+pub struct Coordinator {
+    inner: Arc<Mutex<Inner>>,
+    job_available: Arc<Notify>,
+    stdin_available: Arc<Notify>,
+    events: JobEvents,
+    jobs: JobRegistry,
+    token: String,
+}
+
+impl Coordinator {
+    pub fn new(token: String, events: JobEvents, jobs: JobRegistry) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            job_available: Arc::new(Notify::new()),
+            stdin_available: Arc::new(Notify::new()),
+            events,
+            jobs,
+            token,
+        }
+    }
+
+    /// Compares in constant time, so the token can't be guessed byte by byte.
+    pub fn authorize(&self, bearer: Option<&str>) -> bool {
+        bearer.is_some_and(|b| bool::from(b.as_bytes().ct_eq(self.token.as_bytes())))
+    }
+
+    /// Queues a job and waits until a worker reports its result.
+    pub async fn execute(&self, job_id: &str, language: Language, code: String, options: RunOptions) -> Result<String, String> {
+        let job_id = job_id.to_string();
+        let (tx, rx) = oneshot::channel();
+
+        self.jobs.submit(&job_id, language.clone(), &code).await;
+        self.events.open(&job_id).await;
+        {
+            let mut inner = self.inner.lock().await;
+            let live = |w: &WorkerInfo| w.last_seen.elapsed() <= WORKER_TIMEOUT;
+            if !inner.workers.values().any(|w| live(w) && w.languages.contains(&language)) {
+                drop(inner);
+                self.events.close(&job_id).await;
+                let err = format!("No worker available for {:?}", language);
+                self.jobs.transition(&job_id, JobState::Failed, Some(err.clone())).await;
+                return Err(err);
+            }
+            inner.waiters.insert(job_id.clone(), tx);
+            inner.queue.push_back(PendingJob {
+                assignment: JobAssignment { job_id: job_id.clone(), language, code, options },
+                attempts: 0,
+                queued_at: Instant::now(),
+            });
+        }
+        tracing::info!("[Job {}] Queued for cluster execution", job_id);
+        self.job_available.notify_waiters();
+
+        let result = rx.await.unwrap_or_else(|_| Err("Coordinator dropped the job".to_string()));
+        self.events.close(&job_id).await;
+        result
+    }
+
+    pub async fn register(&self, req: RegisterRequest) -> RegisterResponse {
+        let worker_id = Uuid::new_v4().to_string();
+        tracing::info!(
+            "Worker {} ({}) registered: capacity {}, languages {:?}",
+            worker_id, req.name, req.capacity, req.languages
+        );
+        self.inner.lock().await.workers.insert(worker_id.clone(), WorkerInfo {
+            name: req.name,
+            capacity: req.capacity.max(1),
+            languages: req.languages,
+            last_seen: Instant::now(),
+            running: HashSet::new(),
+        });
+        // A new worker may be able to take jobs nobody else could
+        self.job_available.notify_waiters();
+        RegisterResponse {
+            worker_id,
+            heartbeat_interval_secs: HEARTBEAT_INTERVAL.as_secs(),
+        }
+    }
+
+    /// Returns the worker's jobs it should cancel, or `None` if the worker is
+    /// unknown (e.g. it was declared dead) and must re-register.
+    pub async fn heartbeat(&self, worker_id: &str) -> Option<Vec<String>> {
+        let mut guard = self.inner.lock().await;
+        let inner = &mut *guard;
+        let w = inner.workers.get_mut(worker_id)?;
+        w.last_seen = Instant::now();
+        Some(w.running.iter().filter(|job| inner.cancelling.contains(*job)).cloned().collect())
+    }
+
+    /// Queues input for an interactive job until its worker picks it up.
+    pub async fn write_stdin(&self, job_id: &str, input: StdinInput) -> Result<(), String> {
+        let mut guard = self.inner.lock().await;
+        let inner = &mut *guard;
+        let job = inner.queue.iter()
+            .find(|j| j.assignment.job_id == job_id)
+            .or_else(|| inner.assigned.get(job_id).map(|(_, job)| job))
+            .ok_or_else(|| format!("Job {} is not running", job_id))?;
+        job.assignment.options.check_input(job_id, &input)?;
+        inner.stdin.entry(job_id.to_string()).or_default().push_back(input);
+        drop(guard);
+        self.stdin_available.notify_waiters();
+        Ok(())
+    }
+
+    /// Long-polls for input to a job the worker owns. `Err` means it doesn't own it (anymore).
+    pub async fn poll_stdin(&self, worker_id: &str, job_id: &str) -> Result<Vec<StdinInput>, ()> {
+        let deadline = Instant::now() + POLL_TIMEOUT;
+        loop {
+            let notified = self.stdin_available.notified();
+            {
+                let mut inner = self.inner.lock().await;
+                match inner.assigned.get(job_id) {
+                    Some((owner, _)) if owner == worker_id => {}
+                    _ => return Err(()),
+                }
+                if let Some(queued) = inner.stdin.remove(job_id) {
+                    return Ok(queued.into());
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(Vec::new());
+            }
+            let _ = tokio::time::timeout(deadline - now, notified).await;
+        }
+    }
+
+    /// Cancels a queued job right away. A job already on a worker is cancelled
+    /// there after its next heartbeat and reported back through `complete`.
+    pub async fn cancel(&self, job_id: &str) -> Result<(), String> {
+        let mut inner = self.inner.lock().await;
+        if let Some(pos) = inner.queue.iter().position(|j| j.assignment.job_id == job_id) {
+            inner.queue.remove(pos);
+            inner.stdin.remove(job_id);
+            let waiter = inner.waiters.remove(job_id);
+            drop(inner);
+            tracing::info!("[Job {}] Cancelled while queued", job_id);
+            self.jobs.transition(job_id, JobState::Cancelled, None).await;
+            if let Some(waiter) = waiter {
+                let _ = waiter.send(Err("Job cancelled".to_string()));
+            }
+            return Ok(());
+        }
+        if inner.assigned.contains_key(job_id) {
+            tracing::info!("[Job {}] Cancel requested, waiting for its worker", job_id);
+            inner.cancelling.insert(job_id.to_string());
+            return Ok(());
+        }
+        Err(format!("Job {} is not running", job_id))
+    }
+
+    /// Long-polls for the next job this worker can take. `Err` means the worker is unknown.
+    pub async fn poll(&self, worker_id: &str) -> Result<Option<JobAssignment>, ()> {
+        let deadline = Instant::now() + POLL_TIMEOUT;
+        loop {
+            // Register interest before checking so a notify between check and wait isn't lost
+            let notified = self.job_available.notified();
+            {
+                let mut guard = self.inner.lock().await;
+                let inner = &mut *guard;
+                let worker = inner.workers.get_mut(worker_id).ok_or(())?;
+                worker.last_seen = Instant::now();
+
+                if worker.running.len() < worker.capacity {
+                    let pos = inner.queue.iter().position(|j| worker.languages.contains(&j.assignment.language));
+                    if let Some(job) = pos.and_then(|p| inner.queue.remove(p)) {
+                        let assignment = job.assignment.clone();
+                        worker.running.insert(assignment.job_id.clone());
+                        inner.assigned.insert(assignment.job_id.clone(), (worker_id.to_string(), job));
+                        tracing::info!("[Job {}] Assigned to worker {}", assignment.job_id, worker_id);
+                        let worker_name = worker.name.clone();
+                        drop(guard);
+                        self.jobs.update(&assignment.job_id, |r| r.worker = Some(worker_name.clone())).await;
+                        self.jobs.transition(&assignment.job_id, JobState::Running, Some(format!("assigned to {}", worker_name))).await;
+                        return Ok(Some(assignment));
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let _ = tokio::time::timeout(deadline - now, notified).await;
+        }
+    }
+
+    /// Relays events a worker streamed for a job it owns.
+    pub async fn publish(&self, worker_id: &str, job_id: &str, events: &[Value]) -> bool {
+        if !self.owns(worker_id, job_id).await {
+            return false;
+        }
+        for event in events {
+            self.events.publish(job_id, event).await;
+        }
+        true
+    }
+
+    pub async fn complete(&self, worker_id: &str, job_id: &str, result: Result<String, String>, record: Option<JobRecord>) -> bool {
+        let mut inner = self.inner.lock().await;
+        match inner.assigned.get(job_id) {
+            Some((owner, _)) if owner == worker_id => {}
+            _ => return false,
+        }
+        inner.assigned.remove(job_id);
+        if let Some(w) = inner.workers.get_mut(worker_id) {
+            w.running.remove(job_id);
+        }
+        let waiter = inner.waiters.remove(job_id);
+        let cancelled = inner.cancelling.remove(job_id);
+        inner.stdin.remove(job_id);
+        drop(inner);
+        let result = if cancelled { Err("Job cancelled".to_string()) } else { result };
+
+        // Keep our record in sync with what the worker saw
+        if let Some(remote) = record {
+            self.jobs.update(job_id, |r| {
+                r.limits = remote.limits;
+                r.exit_code = remote.exit_code;
+                r.trace_location = remote.trace_location;
+            }).await;
+        }
+        match &result {
+            _ if cancelled => self.jobs.transition(job_id, JobState::Cancelled, None).await,
+            Ok(_) => self.jobs.transition(job_id, JobState::Completed, None).await,
+            Err(e) => self.jobs.transition(job_id, JobState::Failed, Some(e.clone())).await,
+        }
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(result);
+        }
+        // The worker has a free slot again
+        self.job_available.notify_waiters();
+        true
+    }
+
+    pub async fn workers(&self) -> Vec<WorkerStatus> {
+        let inner = self.inner.lock().await;
+        inner.workers.iter().map(|(id, w)| WorkerStatus {
+            worker_id: id.clone(),
+            name: w.name.clone(),
+            capacity: w.capacity,
+            running: w.running.len(),
+            languages: w.languages.clone(),
+            last_seen_secs: w.last_seen.elapsed().as_secs(),
+        }).collect()
+    }
+
+    async fn owns(&self, worker_id: &str, job_id: &str) -> bool {
+        let inner = self.inner.lock().await;
+        matches!(inner.assigned.get(job_id), Some((owner, _)) if owner == worker_id)
+    }
+
+    /// Drops workers that stopped heart-beating and re-queues their jobs. Queued
+    /// jobs that no remaining worker can run, or that waited past `QUEUE_TIMEOUT`,
+    /// are failed.
+    pub async fn reap_dead_workers(&self) {
+        let mut failed = Vec::new();
+        let mut requeued = Vec::new();
+        let mut cancelled = Vec::new();
+        let mut unschedulable = Vec::new();
+        {
+            let mut inner = self.inner.lock().await;
+            let dead: Vec<String> = inner.workers.iter()
+                .filter(|(_, w)| w.last_seen.elapsed() > WORKER_TIMEOUT)
+                .map(|(id, _)| id.clone())
+                .collect();
+
+            for worker_id in dead {
+                let Some(worker) = inner.workers.remove(&worker_id) else { continue };
+                tracing::warn!("Worker {} ({}) missed its heartbeats, removing", worker_id, worker.name);
+
+                for job_id in worker.running {
+                    let Some((_, mut job)) = inner.assigned.remove(&job_id) else { continue };
+                    job.attempts += 1;
+                    // A restarted program starts over, old input would not fit
+                    inner.stdin.remove(&job_id);
+                    if inner.cancelling.remove(&job_id) {
+                        // It was going to stop anyway, don't run it again
+                        if let Some(waiter) = inner.waiters.remove(&job_id) {
+                            let _ = waiter.send(Err("Job cancelled".to_string()));
+                        }
+                        cancelled.push(job_id);
+                    } else if job.attempts >= MAX_ATTEMPTS {
+                        if let Some(waiter) = inner.waiters.remove(&job_id) {
+                            let _ = waiter.send(Err(format!("Job lost on {} workers, giving up", job.attempts)));
+                        }
+                        failed.push(job_id);
+                    } else {
+                        // Retried jobs go first, they have waited longest
+                        job.queued_at = Instant::now();
+                        inner.queue.push_front(job);
+                        requeued.push(job_id);
+                    }
+                }
+            }
+
+            let mut kept = VecDeque::with_capacity(inner.queue.len());
+            while let Some(job) = inner.queue.pop_front() {
+                let language = &job.assignment.language;
+                let reason = if !inner.workers.values().any(|w| w.languages.contains(language)) {
+                    format!("No worker available for {:?}", language)
+                } else if job.queued_at.elapsed() > QUEUE_TIMEOUT {
+                    format!("No worker picked the job up within {}s", QUEUE_TIMEOUT.as_secs())
+                } else {
+                    kept.push_back(job);
+                    continue;
+                };
+                let job_id = job.assignment.job_id;
+                inner.stdin.remove(&job_id);
+                if let Some(waiter) = inner.waiters.remove(&job_id) {
+                    let _ = waiter.send(Err(reason.clone()));
+                }
+                unschedulable.push((job_id, reason));
+            }
+            inner.queue = kept;
+            requeued.retain(|id| !unschedulable.iter().any(|(job_id, _)| job_id == id));
+        }
+
+        for job_id in &requeued {
+            tracing::info!("[Job {}] Re-queued after worker loss", job_id);
+            self.jobs.transition(job_id, JobState::Queued, Some("worker lost, re-queued".to_string())).await;
+            self.events.publish(job_id, &serde_json::json!({
+                "type": "Requeued",
+                "content": "Worker lost, job restarted on another worker"
+            })).await;
+        }
+        for job_id in &failed {
+            tracing::error!("[Job {}] Failed after repeated worker loss", job_id);
+            self.jobs.transition(job_id, JobState::Failed, Some("lost on too many workers".to_string())).await;
+        }
+        for job_id in &cancelled {
+            self.jobs.transition(job_id, JobState::Cancelled, Some("worker lost".to_string())).await;
+        }
+        for (job_id, reason) in unschedulable {
+            tracing::warn!("[Job {}] {}, failing it", job_id, reason);
+            self.jobs.transition(&job_id, JobState::Failed, Some(reason)).await;
+        }
+        if !requeued.is_empty() {
+            self.job_available.notify_waiters();
+        }
+    }
+
+    /// Background task that periodically reaps dead workers.
+    pub fn spawn_reaper(&self) {
+        let coordinator = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                coordinator.reap_dead_workers().await;
+            }
+        });
+    }
+}
@@ -0,0 +1,133 @@
+pub mod coordinator;
+pub mod worker;
+
+use crate::docker::manager::{Language, RunOptions};
+use crate::jobs::registry::JobRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+
+/// How this syscore process participates in a cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClusterMode {
+    /// Runs jobs on the local engine (the default, single host)
+    Standalone,
+    /// Accepts jobs on the public routes and hands them to registered workers
+    Coordinator,
+    /// Runs jobs handed out by a coordinator (and still serves the local API)
+    Worker,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    pub mode: ClusterMode,
+    /// Shared secret workers present as `Authorization: Bearer <token>`
+    pub token: String,
+    pub coordinator_url: String,
+    pub worker_name: String,
+    pub capacity: usize,
+    pub languages: Vec<Language>,
+}
+
+impl ClusterConfig {
+    /// Reads `SYSCORE_MODE` (`standalone` | `coordinator` | `worker`) and the related
+    /// `SYSCORE_CLUSTER_TOKEN`, `SYSCORE_COORDINATOR_URL`, `SYSCORE_WORKER_NAME`,
+    /// `SYSCORE_WORKER_CAPACITY` and `SYSCORE_WORKER_LANGUAGES` variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mode = match env::var("SYSCORE_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "" | "standalone" => ClusterMode::Standalone,
+            "coordinator" => ClusterMode::Coordinator,
+            "worker" => ClusterMode::Worker,
+            other => return Err(format!("Unknown SYSCORE_MODE: {}", other)),
+        };
+
+        let token = env::var("SYSCORE_CLUSTER_TOKEN").unwrap_or_default();
+        if mode != ClusterMode::Standalone && token.is_empty() {
+            return Err("SYSCORE_CLUSTER_TOKEN must be set in coordinator and worker mode".to_string());
+        }
+
+        let coordinator_url = env::var("SYSCORE_COORDINATOR_URL").unwrap_or_default();
+        if mode == ClusterMode::Worker && coordinator_url.is_empty() {
+            return Err("SYSCORE_COORDINATOR_URL must be set in worker mode".to_string());
+        }
+
+        let worker_name = env::var("SYSCORE_WORKER_NAME")
+            .or_else(|_| env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "syscore-worker".to_string());
+
+        let capacity = match env::var("SYSCORE_WORKER_CAPACITY") {
+            Ok(v) => v.parse::<usize>().ok().filter(|c| *c > 0)
+                .ok_or_else(|| format!("Invalid SYSCORE_WORKER_CAPACITY: {}", v))?,
+            Err(_) => 2,
+        };
+
+        let languages = match env::var("SYSCORE_WORKER_LANGUAGES") {
+            Ok(v) => v
+                .split(',')
+                .map(|l| Language::parse(l.trim()).ok_or_else(|| format!("Unknown language in SYSCORE_WORKER_LANGUAGES: {}", l)))
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(_) => vec![Language::Python, Language::Cpp],
+        };
+
+        Ok(Self {
+            mode,
+            token,
+            coordinator_url: coordinator_url.trim_end_matches('/').to_string(),
+            worker_name,
+            capacity,
+            languages,
+        })
+    }
+}
+
+// --- Wire types shared by coordinator and worker ---
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegisterRequest {
+    pub name: String,
+    pub capacity: usize,
+    pub languages: Vec<Language>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegisterResponse {
+    pub worker_id: String,
+    pub heartbeat_interval_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HeartbeatResponse {
+    /// Jobs of this worker that were cancelled and should be stopped
+    #[serde(default)]
+    pub cancel: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobAssignment {
+    pub job_id: String,
+    pub language: Language,
+    pub code: String,
+    #[serde(default)]
+    pub options: RunOptions,
+}
+
+/// Long-poll for input to an interactive job's stdin.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StdinPoll {
+    pub worker_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventBatch {
+    pub worker_id: String,
+    pub events: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobCompletion {
+    pub worker_id: String,
+    pub result: Result<String, String>,
+    /// The worker's own record of the run (limits, exit code, trace location)
+    #[serde(default)]
+    pub record: Option<JobRecord>,
+}
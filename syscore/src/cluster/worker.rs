@@ -0,0 +1,297 @@
+use super::{ClusterConfig, EventBatch, HeartbeatResponse, JobAssignment, JobCompletion, RegisterRequest, RegisterResponse, StdinPoll};
+use crate::docker::manager::{ContainerManager, StdinInput};
+use crate::jobs::events::StreamEvent;
+use reqwest::{Client, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{RwLock, broadcast};
+
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+// Events are forwarded in small batches to keep the request count down
+const EVENT_BATCH_SIZE: usize = 64;
+const EVENT_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Connects this syscore instance to a coordinator and runs the jobs it hands out.
+#[derive(Clone)]
+pub struct Worker {
+    client: Client,
+    config: ClusterConfig,
+    manager: ContainerManager,
+    worker_id: Arc<RwLock<Option<String>>>,
+}
+
+impl Worker {
+    pub fn new(config: ClusterConfig, manager: ContainerManager) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .unwrap_or_default(),
+            config,
+            manager,
+            worker_id: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Starts the heartbeat loop and one poll loop per capacity slot.
+    pub fn spawn(self) {
+        let heartbeat = self.clone();
+        tokio::spawn(async move { heartbeat.heartbeat_loop().await });
+
+        for _ in 0..self.config.capacity {
+            let slot = self.clone();
+            tokio::spawn(async move { slot.poll_loop().await });
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.coordinator_url, path)
+    }
+
+    fn auth(&self) -> String {
+        format!("Bearer {}", self.config.token)
+    }
+
+    /// Returns the current worker id, registering (and retrying until it works) if needed.
+    async fn worker_id(&self) -> String {
+        if let Some(id) = self.worker_id.read().await.clone() {
+            return id;
+        }
+
+        let mut slot = self.worker_id.write().await;
+        // Another loop may have registered while we waited for the lock
+        if let Some(id) = slot.clone() {
+            return id;
+        }
+
+        loop {
+            match self.register().await {
+                Ok(res) => {
+                    tracing::info!("Registered with coordinator {} as worker {}", self.config.coordinator_url, res.worker_id);
+                    *slot = Some(res.worker_id.clone());
+                    return res.worker_id;
+                }
+                Err(e) => {
+                    tracing::warn!("Coordinator registration failed: {}. Retrying...", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn register(&self) -> Result<RegisterResponse, String> {
+        let req = RegisterRequest {
+            name: self.config.worker_name.clone(),
+            capacity: self.config.capacity,
+            languages: self.config.languages.clone(),
+        };
+        let res = self.client
+            .post(self.url("/api/cluster/workers"))
+            .header("Authorization", self.auth())
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("Coordinator answered {}", res.status()));
+        }
+        res.json::<RegisterResponse>().await.map_err(|e| e.to_string())
+    }
+
+    /// Forgets our id so the next call to `worker_id` registers again.
+    async fn reset_registration(&self, stale_id: &str) {
+        let mut slot = self.worker_id.write().await;
+        if slot.as_deref() == Some(stale_id) {
+            tracing::warn!("Coordinator no longer knows worker {}, re-registering", stale_id);
+            *slot = None;
+        }
+    }
+
+    async fn heartbeat_loop(&self) {
+        let mut interval = tokio::time::interval(super::coordinator::HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let worker_id = self.worker_id().await;
+            let res = self.client
+                .post(self.url(&format!("/api/cluster/workers/{}/heartbeat", worker_id)))
+                .header("Authorization", self.auth())
+                .send()
+                .await;
+            match res {
+                Ok(r) if r.status() == StatusCode::NOT_FOUND => self.reset_registration(&worker_id).await,
+                Ok(r) if !r.status().is_success() => tracing::warn!("Heartbeat rejected: {}", r.status()),
+                Ok(r) => {
+                    let reply = r.json::<HeartbeatResponse>().await.unwrap_or_default();
+                    for job_id in reply.cancel {
+                        if let Err(e) = self.manager.cancel(&job_id).await {
+                            tracing::debug!("[Job {}] Cancel from coordinator: {}", job_id, e);
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("Heartbeat failed: {}", e),
+            }
+        }
+    }
+
+    async fn poll_loop(&self) {
+        loop {
+            let worker_id = self.worker_id().await;
+            let res = self.client
+                .post(self.url(&format!("/api/cluster/workers/{}/poll", worker_id)))
+                .header("Authorization", self.auth())
+                .send()
+                .await;
+
+            match res {
+                Ok(r) if r.status() == StatusCode::OK => match r.json::<JobAssignment>().await {
+                    Ok(job) => self.run_job(&worker_id, job).await,
+                    Err(e) => tracing::warn!("Invalid job assignment: {}", e),
+                },
+                Ok(r) if r.status() == StatusCode::NO_CONTENT => {}
+                Ok(r) if r.status() == StatusCode::NOT_FOUND => self.reset_registration(&worker_id).await,
+                Ok(r) => {
+                    tracing::warn!("Poll rejected: {}", r.status());
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(e) => {
+                    tracing::warn!("Poll failed: {}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn run_job(&self, worker_id: &str, job: JobAssignment) {
+        tracing::info!("[Job {}] Received from coordinator", job.job_id);
+
+        // Open the channel ourselves so no early event is missed by the forwarder
+        let rx = self.manager.events().open(&job.job_id).await.subscribe();
+        let forwarder = {
+            let worker = self.clone();
+            let worker_id = worker_id.to_string();
+            let job_id = job.job_id.clone();
+            tokio::spawn(async move { worker.forward_events(&worker_id, &job_id, rx).await })
+        };
+
+        let stdin = job.options.opens_stdin().then(|| {
+            let worker = self.clone();
+            let worker_id = worker_id.to_string();
+            let job_id = job.job_id.clone();
+            tokio::spawn(async move { worker.relay_stdin(&worker_id, &job_id).await })
+        });
+
+        let result = self.manager.execute(&job.job_id, job.language, job.code, job.options).await;
+        // execute closed the channel, so the forwarder drains and exits
+        let _ = forwarder.await;
+        if let Some(stdin) = stdin {
+            stdin.abort();
+        }
+
+        let completion = JobCompletion {
+            worker_id: worker_id.to_string(),
+            result,
+            record: self.manager.jobs().get(&job.job_id).await,
+        };
+        for _ in 0..3 {
+            let res = self.client
+                .post(self.url(&format!("/api/cluster/jobs/{}/complete", job.job_id)))
+                .header("Authorization", self.auth())
+                .json(&completion)
+                .send()
+                .await;
+            match res {
+                Ok(r) if r.status().is_success() => break,
+                Ok(r) => {
+                    // The coordinator re-queued it elsewhere, nothing left to do
+                    tracing::warn!("[Job {}] Completion rejected: {}", job.job_id, r.status());
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!("[Job {}] Completion report failed: {}", job.job_id, e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn forward_events(&self, worker_id: &str, job_id: &str, mut rx: broadcast::Receiver<StreamEvent>) {
+        let mut batch = Vec::new();
+        let mut flush = tokio::time::interval(EVENT_FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Ok(raw) => {
+                        if let Ok(event) = serde_json::from_str(&raw.data) {
+                            batch.push(event);
+                        }
+                        if batch.len() >= EVENT_BATCH_SIZE {
+                            self.send_events(worker_id, job_id, &mut batch).await;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("[Job {}] Forwarder lagged, {} events not streamed", job_id, n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = flush.tick() => {
+                    if !batch.is_empty() {
+                        self.send_events(worker_id, job_id, &mut batch).await;
+                    }
+                }
+            }
+        }
+        if !batch.is_empty() {
+            self.send_events(worker_id, job_id, &mut batch).await;
+        }
+    }
+
+    /// Long-polls the coordinator for input to an interactive job while it runs.
+    async fn relay_stdin(&self, worker_id: &str, job_id: &str) {
+        let body = StdinPoll { worker_id: worker_id.to_string() };
+        loop {
+            let res = self.client
+                .post(self.url(&format!("/api/cluster/jobs/{}/stdin", job_id)))
+                .header("Authorization", self.auth())
+                .json(&body)
+                .send()
+                .await;
+            match res {
+                Ok(r) if r.status() == StatusCode::OK => {
+                    for input in r.json::<Vec<StdinInput>>().await.unwrap_or_default() {
+                        if let Err(e) = self.manager.write_stdin(job_id, input).await {
+                            tracing::warn!("[Job {}] Dropping input: {}", job_id, e);
+                        }
+                    }
+                }
+                Ok(r) if r.status() == StatusCode::NO_CONTENT => {}
+                // The job is no longer ours
+                Ok(r) if r.status() == StatusCode::CONFLICT => return,
+                Ok(r) => {
+                    tracing::warn!("[Job {}] Stdin poll rejected: {}", job_id, r.status());
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(e) => {
+                    tracing::warn!("[Job {}] Stdin poll failed: {}", job_id, e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn send_events(&self, worker_id: &str, job_id: &str, batch: &mut Vec<serde_json::Value>) {
+        let body = EventBatch {
+            worker_id: worker_id.to_string(),
+            events: std::mem::take(batch),
+        };
+        let res = self.client
+            .post(self.url(&format!("/api/cluster/jobs/{}/events", job_id)))
+            .header("Authorization", self.auth())
+            .json(&body)
+            .send()
+            .await;
+        if let Err(e) = res {
+            // Live streaming is best effort; the full trace is still uploaded at the end
+            tracing::warn!("[Job {}] Event forwarding failed: {}", job_id, e);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use bollard::models::SystemInfo;
+use bollard::system::Version;
+use bollard::{Docker, API_DEFAULT_VERSION};
+use std::env;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// Where the container engine API lives.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EngineEndpoint {
+    /// Platform defaults (`/var/run/docker.sock` or `DOCKER_HOST` as bollard reads it)
+    Local,
+    Unix(String),
+    /// Plain HTTP over TCP, e.g. `tcp://10.0.0.5:2375`
+    Tcp(String),
+    /// HTTPS over TCP with client certificates, e.g. `tcp://10.0.0.5:2376`
+    Tls {
+        addr: String,
+        ca: PathBuf,
+        cert: PathBuf,
+        key: PathBuf,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub endpoint: EngineEndpoint,
+    pub timeout_secs: u64,
+    /// Run jobs even when the engine cannot enforce memory/CPU limits
+    pub allow_unlimited: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: EngineEndpoint::Local,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            allow_unlimited: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Reads the engine endpoint from the environment.
+    ///
+    /// - `SYSCORE_ENGINE_HOST` (falls back to `DOCKER_HOST`): `unix://`, `tcp://` or `https://` URL.
+    ///   The special value `podman` resolves to the rootless Podman socket of the current user.
+    /// - `SYSCORE_ENGINE_TLS_CA` / `_CERT` / `_KEY`: client certificate paths. If unset but
+    ///   `DOCKER_CERT_PATH` is, the usual `ca.pem`/`cert.pem`/`key.pem` inside it are used.
+    ///   TLS is enabled when certificates are configured, `DOCKER_TLS_VERIFY` is set, or the
+    ///   host uses `https://`.
+    /// - `SYSCORE_ENGINE_TIMEOUT`: request timeout in seconds (default 120).
+    /// - `SYSCORE_ALLOW_UNLIMITED=1`: start even if the engine cannot enforce memory/CPU limits.
+    pub fn from_env() -> Result<Self, String> {
+        let timeout_secs = match env::var("SYSCORE_ENGINE_TIMEOUT") {
+            Ok(v) => v.parse().map_err(|_| format!("Invalid SYSCORE_ENGINE_TIMEOUT: {}", v))?,
+            Err(_) => DEFAULT_TIMEOUT_SECS,
+        };
+
+        let host = env::var("SYSCORE_ENGINE_HOST")
+            .or_else(|_| env::var("DOCKER_HOST"))
+            .ok()
+            .filter(|h| !h.trim().is_empty());
+
+        let allow_unlimited = env::var("SYSCORE_ALLOW_UNLIMITED")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let endpoint = match host {
+            None => EngineEndpoint::Local,
+            Some(h) if h == "podman" => EngineEndpoint::Unix(rootless_podman_socket()?),
+            Some(h) if h.starts_with("unix://") => {
+                EngineEndpoint::Unix(h.trim_start_matches("unix://").to_string())
+            }
+            Some(h) if h.starts_with('/') => EngineEndpoint::Unix(h),
+            Some(h) if h.starts_with("tcp://") || h.starts_with("http://") || h.starts_with("https://") => {
+                match tls_paths()? {
+                    Some((ca, cert, key)) => EngineEndpoint::Tls { addr: h, ca, cert, key },
+                    None if h.starts_with("https://") => {
+                        return Err("Engine host uses https:// but no client certificates are configured".to_string());
+                    }
+                    None => EngineEndpoint::Tcp(h),
+                }
+            }
+            Some(h) => return Err(format!("Unsupported engine host: {}", h)),
+        };
+
+        Ok(Self { endpoint, timeout_secs, allow_unlimited })
+    }
+
+    pub fn connect(&self) -> Result<Docker, bollard::errors::Error> {
+        match &self.endpoint {
+            EngineEndpoint::Local => Docker::connect_with_local_defaults(),
+            EngineEndpoint::Unix(path) => Docker::connect_with_unix(path, self.timeout_secs, API_DEFAULT_VERSION),
+            EngineEndpoint::Tcp(addr) => Docker::connect_with_http(addr, self.timeout_secs, API_DEFAULT_VERSION),
+            EngineEndpoint::Tls { addr, ca, cert, key } => {
+                Docker::connect_with_ssl(addr, key, cert, ca, self.timeout_secs, API_DEFAULT_VERSION)
+            }
+        }
+    }
+
+    /// Short human readable description for logs (never includes key material).
+    pub fn describe(&self) -> String {
+        match &self.endpoint {
+            EngineEndpoint::Local => "local defaults".to_string(),
+            EngineEndpoint::Unix(path) => format!("unix://{}", path),
+            EngineEndpoint::Tcp(addr) => addr.clone(),
+            EngineEndpoint::Tls { addr, .. } => format!("{} (TLS)", addr),
+        }
+    }
+}
+
+fn rootless_podman_socket() -> Result<String, String> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR")
+        .map_err(|_| "SYSCORE_ENGINE_HOST=podman requires XDG_RUNTIME_DIR to locate the Podman socket".to_string())?;
+    Ok(Path::new(&runtime_dir).join("podman/podman.sock").to_string_lossy().to_string())
+}
+
+fn tls_paths() -> Result<Option<(PathBuf, PathBuf, PathBuf)>, String> {
+    let explicit = (
+        env::var("SYSCORE_ENGINE_TLS_CA").ok(),
+        env::var("SYSCORE_ENGINE_TLS_CERT").ok(),
+        env::var("SYSCORE_ENGINE_TLS_KEY").ok(),
+    );
+
+    match explicit {
+        (Some(ca), Some(cert), Some(key)) => return Ok(Some((ca.into(), cert.into(), key.into()))),
+        (None, None, None) => {}
+        _ => {
+            return Err("SYSCORE_ENGINE_TLS_CA, SYSCORE_ENGINE_TLS_CERT and SYSCORE_ENGINE_TLS_KEY must be set together".to_string());
+        }
+    }
+
+    let verify = env::var("DOCKER_TLS_VERIFY").map(|v| !v.is_empty() && v != "0").unwrap_or(false);
+    match env::var("DOCKER_CERT_PATH") {
+        Ok(dir) => {
+            let dir = PathBuf::from(dir);
+            Ok(Some((dir.join("ca.pem"), dir.join("cert.pem"), dir.join("key.pem"))))
+        }
+        Err(_) if verify => {
+            let home = env::var("HOME").map_err(|_| "DOCKER_TLS_VERIFY is set but HOME is unknown".to_string())?;
+            let dir = PathBuf::from(home).join(".docker");
+            Ok(Some((dir.join("ca.pem"), dir.join("cert.pem"), dir.join("key.pem"))))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineKind {
+    Docker,
+    Podman,
+}
+
+/// What the connected engine can actually enforce. Rootless Podman on cgroup v1
+/// (or without cgroup delegation) rejects memory/CPU limits, so we only request
+/// the ones it reports as supported.
+#[derive(Clone, Debug)]
+pub struct EngineCapabilities {
+    pub kind: EngineKind,
+    pub version: String,
+    pub rootless: bool,
+    pub memory_limit: bool,
+    pub cpu_limit: bool,
+}
+
+impl Default for EngineCapabilities {
+    // Assume a regular rootful Docker daemon until health_check has run
+    fn default() -> Self {
+        Self {
+            kind: EngineKind::Docker,
+            version: String::new(),
+            rootless: false,
+            memory_limit: true,
+            cpu_limit: true,
+        }
+    }
+}
+
+impl EngineCapabilities {
+    pub fn detect(version: &Version, info: &SystemInfo) -> Self {
+        let is_podman = version
+            .components
+            .as_ref()
+            .map(|cs| cs.iter().any(|c| c.name.to_lowercase().contains("podman")))
+            .unwrap_or(false)
+            || version
+                .platform
+                .as_ref()
+                .map(|p| p.name.to_lowercase().contains("podman"))
+                .unwrap_or(false);
+
+        let rootless = info
+            .security_options
+            .as_ref()
+            .map(|opts| opts.iter().any(|o| o.contains("rootless")))
+            .unwrap_or(false);
+
+        Self {
+            kind: if is_podman { EngineKind::Podman } else { EngineKind::Docker },
+            version: version.version.clone().unwrap_or_default(),
+            rootless,
+            // Docker always reports these; Podman omits them when the cgroup controller is missing
+            memory_limit: info.memory_limit.unwrap_or(!is_podman),
+            cpu_limit: info.cpu_cfs_quota.unwrap_or(!is_podman),
+        }
+    }
+}
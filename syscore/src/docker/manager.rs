@@ -1,19 +1,38 @@
 use bollard::Docker;
-use bollard::container::{Config, CreateContainerOptions, LogOutput, LogsOptions};
+use bollard::container::{
+    AttachContainerOptions, AttachContainerResults, Config, CreateContainerOptions, KillContainerOptions, LogOutput, LogsOptions,
+    ResizeContainerTtyOptions,
+};
 use bollard::models::HostConfig;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::{Mutex, broadcast};
-use uuid::Uuid;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use super::engine::{EngineCapabilities, EngineConfig};
+use crate::jobs::events::JobEvents;
+use crate::jobs::registry::{JobRegistry, JobState};
+use crate::server::trace_store::spool::{TraceSpool, UploadOutcome};
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Language {
     Python,
     Cpp,
 }
 
 impl Language {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "python" => Some(Language::Python),
+            "cpp" | "c++" => Some(Language::Cpp),
+            _ => None,
+        }
+    }
+
     fn image_name(&self) -> &str {
         match self {
             Language::Python => "okernel/python-runner",
@@ -22,40 +41,205 @@ impl Language {
     }
 }
 
+/// Per-run switches chosen by the client.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RunOptions {
+    /// Keep the program's stdin open for input sent over `/ws/stream`
+    #[serde(default)]
+    pub interactive: bool,
+    /// Pause at the first line and wait for debugger requests
+    #[serde(default)]
+    pub debug: bool,
+}
+
+impl RunOptions {
+    /// Debug runs take their requests through stdin, so it stays open for them too
+    pub fn opens_stdin(&self) -> bool {
+        self.interactive || self.debug
+    }
+
+    /// Checks that a job started with these options takes `input`.
+    pub fn check_input(&self, job_id: &str, input: &StdinInput) -> Result<(), String> {
+        match input {
+            StdinInput::Debug(_) if !self.debug => Err(format!("Job {} was not started in debug mode", job_id)),
+            _ if !self.opens_stdin() => Err(format!("Job {} was not started as interactive", job_id)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Input for the stdin of an interactive job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum StdinInput {
+    Data(String),
+    Eof,
+    /// Goes to the runner's debugger instead of the program
+    Debug(DebugRequest),
+}
+
+/// Marks a line on a debug job's stdin as a debugger request
+const DEBUG_COMMAND_PREFIX: &str = "__SYSCORE_CMD__";
+
+/// A debugger request, shaped like a subset of the Debug Adapter Protocol.
+/// The runner answers each one with a `Debug` event of kind `response`
+/// carrying `request_seq`, `success` and a `body` or `message`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DebugRequest {
+    #[serde(default)]
+    pub seq: u64,
+    pub command: DebugCommand,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub arguments: serde_json::Value,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DebugCommand {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+    /// `{"breakpoints": [{"line": 3, "condition": "i > 2"}]}`, replacing all earlier ones
+    SetBreakpoints,
+    StackTrace,
+    /// `{"frameId": 0}`, the locals of a frame from `stackTrace` (0 is the innermost)
+    Variables,
+    /// `{"expression": "x + 1", "frameId": 0}`
+    Evaluate,
+}
+
+/// Processes a terminal container may run at once, so a fork bomb stays inside it
+const TERMINAL_PIDS_LIMIT: i64 = 128;
+
+/// An interactive job's stdin is closed after this long without input, so a
+/// program waiting on a client that went away still ends
+const STDIN_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Resource limits applied to a job container (None = the engine can't enforce it).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobLimits {
+    pub memory_bytes: Option<i64>,
+    pub nano_cpus: Option<i64>,
+    pub network_disabled: bool,
+}
+
+impl JobLimits {
+    fn for_engine(caps: &EngineCapabilities) -> Self {
+        Self {
+            memory_bytes: caps.memory_limit.then_some(256 * 1024 * 1024), // 256 MB limit
+            nano_cpus: caps.cpu_limit.then_some(1_000_000_000), // 1 CPU
+            network_disabled: true, // No network access for security
+        }
+    }
+
+    fn host_config(&self) -> HostConfig {
+        HostConfig {
+            memory: self.memory_bytes,
+            nano_cpus: self.nano_cpus,
+            network_mode: self.network_disabled.then(|| "none".to_string()),
+            auto_remove: Some(false), // We remove manually to safely collect logs first
+            ..Default::default()
+        }
+    }
+}
+
+/// An interactive shell in a runner container, attached through a TTY.
+pub struct TerminalSession {
+    pub container: String,
+    /// Raw terminal output (a TTY merges stdout and stderr)
+    pub output: Pin<Box<dyn Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>,
+    pub input: Pin<Box<dyn AsyncWrite + Send>>,
+}
+
+enum JobOutcome {
+    Done,
+    UploadPending,
+}
+
+/// A job between `execute` starting and finishing.
+#[derive(Default)]
+struct RunningJob {
+    // Known once the container was created
+    container: Option<String>,
+    cancelled: bool,
+    // Interactive jobs only; input queues here until the container is attached
+    stdin: Option<mpsc::UnboundedSender<StdinInput>>,
+    options: RunOptions,
+}
+
 #[derive(Clone)]
 pub struct ContainerManager {
     docker: Docker,
-    // We don't need active_containers for per-job isolation anymore
-    // Map of JobID -> Broadcast Sender (kept for potential future streaming)
-    job_channels: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+    // Live event channels per job, shared with the websocket layer
+    events: JobEvents,
+    jobs: JobRegistry,
+    uploads: TraceSpool,
+    running: Arc<Mutex<HashMap<String, RunningJob>>>,
+    // Filled in by health_check once we know whether we talk to Docker or Podman
+    capabilities: Arc<RwLock<EngineCapabilities>>,
+    allow_unlimited: bool,
 }
 
 impl ContainerManager {
-    pub fn new() -> Result<Self, bollard::errors::Error> {
-        let docker = Docker::connect_with_local_defaults()?;
+    pub fn new(
+        config: &EngineConfig,
+        events: JobEvents,
+        jobs: JobRegistry,
+        uploads: TraceSpool,
+    ) -> Result<Self, bollard::errors::Error> {
+        let docker = config.connect()?;
         Ok(Self {
             docker,
-            job_channels: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            jobs,
+            uploads,
+            running: Arc::new(Mutex::new(HashMap::new())),
+            capabilities: Arc::new(RwLock::new(EngineCapabilities::default())),
+            allow_unlimited: config.allow_unlimited,
         })
     }
     
-    /// Verifies Docker connection and ability to run containers
+    /// Verifies the engine connection and ability to run containers, and records
+    /// which resource limits the engine (Docker or Podman) can enforce.
     pub async fn health_check(&self) -> Result<(), String> {
-        tracing::info!("Verifying Docker connection...");
+        tracing::info!("Verifying container engine connection...");
         
         // 1. Check version/ping
-        let version = self.docker.version().await.map_err(|e| format!("Docker ping failed: {}", e))?;
-        tracing::info!("Docker connected: Version {}", version.version.unwrap_or_default());
+        let version = self.docker.version().await.map_err(|e| format!("Engine ping failed: {}", e))?;
+
+        // 2. Feature detection (Podman's compat API answers /info too)
+        let info = self.docker.info().await.map_err(|e| format!("Engine info failed: {}", e))?;
+        let caps = EngineCapabilities::detect(&version, &info);
+        tracing::info!(
+            "{:?} connected: Version {} (rootless: {})",
+            caps.kind, caps.version, caps.rootless
+        );
+        if !(caps.memory_limit && caps.cpu_limit) {
+            // Untrusted code must not run unbounded unless the operator asked for it
+            if !self.allow_unlimited {
+                return Err(format!(
+                    "{:?} cannot enforce memory/CPU limits here (cgroup v2 delegation missing?). \
+                     Set SYSCORE_ALLOW_UNLIMITED=1 to run jobs without them.",
+                    caps.kind
+                ));
+            }
+            tracing::warn!("{:?} cannot enforce memory/CPU limits here; SYSCORE_ALLOW_UNLIMITED is set, jobs will run without them.", caps.kind);
+        }
 
-        // 2. Check if we can list images (basic permission check)
+        // 3. Check if we can list images (basic permission check)
         self.docker.list_images::<String>(None).await.map_err(|e| format!("Failed to list images (permission error?): {}", e))?;
 
+        *self.capabilities.write().await = caps;
         Ok(())
     }
 
-    pub async fn subscribe(&self, job_id: &str) -> Option<broadcast::Receiver<String>> {
-        let channels = self.job_channels.lock().await;
-        channels.get(job_id).map(|sender| sender.subscribe())
+    pub fn events(&self) -> &JobEvents {
+        &self.events
+    }
+
+    pub fn jobs(&self) -> &JobRegistry {
+        &self.jobs
     }
 
     /// Checks if image exists, builds it if not
@@ -69,9 +253,76 @@ impl ContainerManager {
     }
 
     /// Executes code by spawning a bespoke container, running it, collecting output, and destroying it.
-    pub async fn execute(&self, lang: Language, code: String) -> Result<String, String> {
-        let job_id = Uuid::new_v4().to_string();
+    /// The job id is chosen by the caller so it can be handed out before the run finishes.
+    pub async fn execute(&self, job_id: &str, lang: Language, code: String, options: RunOptions) -> Result<String, String> {
         tracing::info!("[Job {}] Starting execution for {:?}", job_id, lang);
+        self.jobs.submit(job_id, lang.clone(), &code).await;
+        self.events.open(job_id).await;
+        let (stdin, stdin_rx) = match options.opens_stdin() {
+            true => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (Some(tx), Some(rx))
+            }
+            false => (None, None),
+        };
+        let debug = options.debug;
+        self.running.lock().await.insert(job_id.to_string(), RunningJob { stdin, options, ..Default::default() });
+        let result = self.run_container(job_id, lang, code, stdin_rx, debug).await;
+        let cancelled = self.running.lock().await.remove(job_id).is_some_and(|job| job.cancelled);
+        self.events.close(job_id).await;
+
+        match (&result, cancelled) {
+            // The spool finishes the job once the trace is delivered
+            (Ok(JobOutcome::UploadPending), _) => self.jobs.transition(job_id, JobState::UploadPending, None).await,
+            (_, true) => {
+                self.jobs.transition(job_id, JobState::Cancelled, None).await;
+                return Err("Job cancelled".to_string());
+            }
+            (Ok(JobOutcome::Done), _) => self.jobs.transition(job_id, JobState::Completed, None).await,
+            (Err(e), _) => self.jobs.transition(job_id, JobState::Failed, Some(e.clone())).await,
+        }
+        result.map(|_| job_id.to_string())
+    }
+
+    /// Queues input for an interactive job's stdin.
+    pub async fn write_stdin(&self, job_id: &str, input: StdinInput) -> Result<(), String> {
+        let running = self.running.lock().await;
+        let job = running.get(job_id).ok_or_else(|| format!("Job {} is not running", job_id))?;
+        job.options.check_input(job_id, &input)?;
+        let stdin = job.stdin.as_ref().ok_or_else(|| format!("Job {} was not started as interactive", job_id))?;
+        stdin.send(input).map_err(|_| format!("Stdin of job {} is closed", job_id))
+    }
+
+    /// Stops a job running on this instance by killing its container. The
+    /// run then winds down as usual (the partial trace is still uploaded) and
+    /// ends up `cancelled`.
+    pub async fn cancel(&self, job_id: &str) -> Result<(), String> {
+        let container = {
+            let mut running = self.running.lock().await;
+            let job = running.get_mut(job_id).ok_or_else(|| format!("Job {} is not running", job_id))?;
+            job.cancelled = true;
+            job.container.clone()
+        };
+        tracing::info!("[Job {}] Cancel requested", job_id);
+        // Without a container yet, run_container stops before starting one
+        if let Some(id) = container {
+            if let Err(e) = self.docker.kill_container(&id, None::<KillContainerOptions<String>>).await {
+                // Most likely it exited on its own in the meantime
+                tracing::warn!("[Job {}] Failed to kill container: {}", job_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_container(
+        &self,
+        job_id: &str,
+        lang: Language,
+        code: String,
+        stdin: Option<mpsc::UnboundedReceiver<StdinInput>>,
+        debug: bool,
+    ) -> Result<JobOutcome, String> {
+        let job_id = job_id.to_string();
 
         // 1. Ensure Image
         self.ensure_image(lang.clone()).await?;
@@ -83,21 +334,22 @@ impl ContainerManager {
         };
 
         // 3. Configure Container (Ephemeral)
-        let host_config = HostConfig {
-            memory: Some(256 * 1024 * 1024), // 256 MB limit
-            nano_cpus: Some(1_000_000_000), // 1 CPU
-            network_mode: Some("none".to_string()), // No network access for security
-            auto_remove: Some(false), // We remove manually to safely collect logs first
-            ..Default::default()
-        };
+        let limits = JobLimits::for_engine(&*self.capabilities.read().await);
+        let host_config = limits.host_config();
 
         // The container will run the command and then exit (because python script finishes)
         let config = Config {
             image: Some(lang.image_name()),
             cmd: Some(cmd.iter().map(|s| s.as_str()).collect()),
+            // The runner hands control to its debugger instead of tracing every step
+            env: debug.then(|| vec!["SYSCORE_DEBUG=1"]),
             attach_stdout: Some(true),
             attach_stderr: Some(true),
-            tty: Some(false), // Non-interactive
+            // Interactive runs get a stdin that closes once we detach from it
+            open_stdin: Some(stdin.is_some()),
+            attach_stdin: Some(stdin.is_some()),
+            stdin_once: Some(stdin.is_some()),
+            tty: Some(false),
             host_config: Some(host_config),
             ..Default::default()
         };
@@ -114,30 +366,52 @@ impl ContainerManager {
             config,
         ).await.map_err(|e| format!("Failed to create container: {}", e))?.id;
 
+        let cancelled = match self.running.lock().await.get_mut(&job_id) {
+            Some(job) => {
+                job.container = Some(id.clone());
+                job.cancelled
+            }
+            None => false,
+        };
+        if cancelled {
+            let _ = self.cleanup_container(&id).await;
+            return Err("Job cancelled".to_string());
+        }
+
+        // Input the client sends is recorded in the trace as Stdin events
+        let (echo_tx, mut echo_rx) = mpsc::unbounded_channel();
+        if let Some(stdin) = stdin {
+            let attached = self.docker.attach_container(&id, Some(AttachContainerOptions::<String> {
+                stdin: Some(true),
+                stream: Some(true),
+                ..Default::default()
+            })).await;
+            match attached {
+                Ok(AttachContainerResults { input, .. }) => {
+                    tokio::spawn(pump_stdin(job_id.clone(), input, stdin, echo_tx));
+                }
+                Err(e) => {
+                    let _ = self.cleanup_container(&id).await;
+                    return Err(format!("Failed to attach stdin: {}", e));
+                }
+            }
+        }
+
         // 5. Start Container
-        self.docker.start_container::<String>(&id, None).await
-            .map_err(|e| {
-                // Cleanup if start fails
-                let _ = self.cleanup_container(&id); 
-                format!("Failed to start container: {}", e)
-            })?;
+        if let Err(e) = self.docker.start_container::<String>(&id, None).await {
+            // Cleanup if start fails
+            let _ = self.cleanup_container(&id).await;
+            return Err(format!("Failed to start container: {}", e));
+        }
 
         tracing::info!("[Job {}] Container started via Spawn->Run strategy", job_id);
+        self.jobs.update(&job_id, |r| r.limits = Some(limits)).await;
+        self.jobs.transition(&job_id, JobState::Running, None).await;
 
-        // 6. Wait for execution to finish
-        // We accept exit code 0 or any other code (user code might crash)
-        let wait_res = self.docker.wait_container::<String>(&id, None).next().await;
-        
-        if let Some(Ok(res)) = wait_res {
-             tracing::debug!("[Job {}] Container exited with code {}", job_id, res.status_code);
-        } else {
-             // If wait fails, likely container error or timeout?
-             tracing::warn!("[Job {}] Wait failed or container crashed specifically", job_id);
-        }
-        
-        // 7. Collect Logs (Trace Events)
-        // Since the container has stopped, we can read all logs at once
+        // 6. Stream Logs (Trace Events) while the container runs
+        // Each event is published live for /ws/stream and kept for the trace upload
         let logs_opts = LogsOptions::<String> {
+            follow: true,
             stdout: true,
             stderr: true,
             ..Default::default()
@@ -145,26 +419,29 @@ impl ContainerManager {
         
         let mut trace_events = Vec::new();
         let mut log_stream = self.docker.logs(&id, Some(logs_opts));
+        // Docker may split long lines across frames, so carry partial lines over
+        let mut pending = String::new();
 
-        while let Some(msg) = log_stream.next().await {
+        loop {
+            let msg = tokio::select! {
+                msg = log_stream.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                Some(event) = echo_rx.recv() => {
+                    self.events.publish(&job_id, &event).await;
+                    trace_events.push(event);
+                    continue;
+                }
+            };
             match msg {
                 Ok(LogOutput::StdOut { message }) | Ok(LogOutput::StdErr { message }) => {
-                    let log_str = String::from_utf8_lossy(&message);
-                    for line in log_str.split('\n') {
-                         if line.is_empty() { continue; }
-                         if line.contains("__SYSCORE_EVENT__") {
-                            if let Some(json_str) = line.split("__SYSCORE_EVENT__").nth(1) {
-                                if let Ok(event) = serde_json::from_str::<serde_json::Value>(json_str) {
-                                    trace_events.push(event);
-                                }
-                            }
-                        } else {
-                             // Capture standard output meant for user
-                            let log_event = serde_json::json!({
-                                "type": "Stdout",
-                                "content": line
-                            });
-                            trace_events.push(log_event);
+                    pending.push_str(&String::from_utf8_lossy(&message));
+                    while let Some(pos) = pending.find('\n') {
+                        let line: String = pending.drain(..=pos).collect();
+                        if let Some(event) = parse_output_line(line.trim_end_matches('\n')) {
+                            self.events.publish(&job_id, &event).await;
+                            trace_events.push(event);
                         }
                     }
                 },
@@ -172,6 +449,31 @@ impl ContainerManager {
                 Err(e) => tracing::warn!("[Job {}] Log retrieval error: {}", job_id, e),
             }
         }
+        if let Some(event) = parse_output_line(&pending) {
+            self.events.publish(&job_id, &event).await;
+            trace_events.push(event);
+        }
+        while let Ok(event) = echo_rx.try_recv() {
+            self.events.publish(&job_id, &event).await;
+            trace_events.push(event);
+        }
+
+        // 7. Wait for execution to finish
+        // We accept exit code 0 or any other code (user code might crash)
+        let wait_res = self.docker.wait_container::<String>(&id, None).next().await;
+        
+        let exit_code = match wait_res {
+            Some(Ok(res)) => Some(res.status_code),
+            // bollard reports non-zero exits as an error carrying the code
+            Some(Err(bollard::errors::Error::DockerContainerWaitError { code, .. })) => Some(code),
+            _ => None,
+        };
+        match exit_code {
+            // If wait fails, likely container error or timeout?
+            None => tracing::warn!("[Job {}] Wait failed or container crashed specifically", job_id),
+            Some(code) => tracing::debug!("[Job {}] Container exited with code {}", job_id, code),
+        }
+        self.jobs.update(&job_id, |r| r.exit_code = exit_code).await;
 
         // 8. Cleanup (Destroy)
         tracing::debug!("[Job {}] Destroying container {}", job_id, id);
@@ -182,14 +484,92 @@ impl ContainerManager {
         // 9. Upload Results
         if !trace_events.is_empty() {
             tracing::info!("[Job {}] Uploading {} trace events", job_id, trace_events.len());
-            if let Err(e) = crate::server::trace_store::upload_trace(&job_id, trace_events).await {
-                 return Err(format!("Trace upload failed: {}", e));
+            self.jobs.transition(&job_id, JobState::Uploading, None).await;
+            match self.uploads.upload(&job_id, &trace_events).await {
+                Ok(UploadOutcome::Stored(location)) => {
+                    self.jobs.update(&job_id, |r| r.trace_location = Some(location)).await;
+                }
+                // The run itself was fine, the spool delivers the trace later
+                Ok(UploadOutcome::Spooled) => return Ok(JobOutcome::UploadPending),
+                // An admin took the trace down before it was stored
+                Ok(UploadOutcome::Discarded) => {}
+                Err(e) => return Err(format!("Trace upload failed: {}", e)),
             }
         } else {
             tracing::warn!("[Job {}] No output collected from container", job_id);
         }
 
-        Ok(job_id)
+        Ok(JobOutcome::Done)
+    }
+
+    /// Starts a shell in a fresh `lang` runner container with a TTY of
+    /// `cols`x`rows`, under the same limits as jobs. A shell runs whatever
+    /// the client types, so it also gets a process limit, no capabilities and
+    /// no way to gain privileges, as the image's unprivileged user.
+    pub async fn start_terminal(&self, lang: Language, cols: u16, rows: u16) -> Result<TerminalSession, String> {
+        self.ensure_image(lang.clone()).await?;
+
+        let limits = JobLimits::for_engine(&*self.capabilities.read().await);
+        let config = Config {
+            image: Some(lang.image_name()),
+            cmd: Some(vec!["/bin/bash", "-l"]),
+            env: Some(vec!["TERM=xterm-256color"]),
+            user: Some("runner"),
+            attach_stdin: Some(true),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            open_stdin: Some(true),
+            tty: Some(true),
+            host_config: Some(HostConfig {
+                pids_limit: Some(TERMINAL_PIDS_LIMIT),
+                cap_drop: Some(vec!["ALL".to_string()]),
+                security_opt: Some(vec!["no-new-privileges".to_string()]),
+                ..limits.host_config()
+            }),
+            ..Default::default()
+        };
+        let name = format!("okernel-term-{}", uuid::Uuid::new_v4());
+        let id = self.docker.create_container(Some(CreateContainerOptions { name, platform: None }), config)
+            .await.map_err(|e| format!("Failed to create container: {}", e))?.id;
+
+        // Attach before starting so the shell's first prompt isn't lost
+        let attached = self.docker.attach_container(&id, Some(AttachContainerOptions::<String> {
+            stdin: Some(true),
+            stdout: Some(true),
+            stderr: Some(true),
+            stream: Some(true),
+            ..Default::default()
+        })).await;
+        let AttachContainerResults { output, input } = match attached {
+            Ok(attached) => attached,
+            Err(e) => {
+                let _ = self.cleanup_container(&id).await;
+                return Err(format!("Failed to attach to container: {}", e));
+            }
+        };
+        if let Err(e) = self.docker.start_container::<String>(&id, None).await {
+            let _ = self.cleanup_container(&id).await;
+            return Err(format!("Failed to start container: {}", e));
+        }
+
+        let session = TerminalSession { container: id, output, input };
+        if let Err(e) = self.resize_terminal(&session.container, cols, rows).await {
+            tracing::debug!("Initial terminal resize failed: {}", e);
+        }
+        tracing::info!("Terminal session started in container {}", session.container);
+        Ok(session)
+    }
+
+    pub async fn resize_terminal(&self, container: &str, cols: u16, rows: u16) -> Result<(), String> {
+        self.docker.resize_container_tty(container, ResizeContainerTtyOptions { width: cols, height: rows })
+            .await.map_err(|e| format!("Failed to resize terminal: {}", e))
+    }
+
+    /// Destroys the session's container, whatever is still running in it.
+    pub async fn end_terminal(&self, session: TerminalSession) {
+        if let Err(e) = self.cleanup_container(&session.container).await {
+            tracing::error!("Failed to remove terminal container {}: {}", session.container, e);
+        }
     }
 
     async fn cleanup_container(&self, id: &str) -> Result<(), bollard::errors::Error> {
@@ -282,3 +662,71 @@ impl ContainerManager {
         Ok(())
     }
 }
+
+/// Feeds client input to the container's stdin until EOF, the idle timeout,
+/// or the job ending (which drops the sender).
+async fn pump_stdin(
+    job_id: String,
+    mut input: Pin<Box<dyn AsyncWrite + Send>>,
+    mut rx: mpsc::UnboundedReceiver<StdinInput>,
+    echo: mpsc::UnboundedSender<serde_json::Value>,
+) {
+    loop {
+        let next = match tokio::time::timeout(STDIN_IDLE_TIMEOUT, rx.recv()).await {
+            Ok(Some(next)) => next,
+            Ok(None) => return,
+            Err(_) => {
+                tracing::info!("[Job {}] No input for {}s, closing stdin", job_id, STDIN_IDLE_TIMEOUT.as_secs());
+                StdinInput::Eof
+            }
+        };
+        let timestamp = chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0);
+        match next {
+            StdinInput::Data(data) => {
+                let written = match input.write_all(data.as_bytes()).await {
+                    Ok(()) => input.flush().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = written {
+                    tracing::warn!("[Job {}] Failed to write stdin: {}", job_id, e);
+                    return;
+                }
+                let _ = echo.send(serde_json::json!({"type": "Stdin", "content": data, "timestamp": timestamp}));
+            }
+            StdinInput::Debug(request) => {
+                let line = format!("{}{}\n", DEBUG_COMMAND_PREFIX, serde_json::to_string(&request).unwrap_or_default());
+                let written = match input.write_all(line.as_bytes()).await {
+                    Ok(()) => input.flush().await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = written {
+                    tracing::warn!("[Job {}] Failed to send debugger request: {}", job_id, e);
+                    return;
+                }
+            }
+            StdinInput::Eof => {
+                let _ = input.shutdown().await;
+                let _ = echo.send(serde_json::json!({"type": "Stdin", "content": "", "eof": true, "timestamp": timestamp}));
+                return;
+            }
+        }
+    }
+}
+
+/// Turns one line of container output into a trace event. Runner events carry the
+/// `__SYSCORE_EVENT__` marker, anything else is plain stdout meant for the user.
+fn parse_output_line(line: &str) -> Option<serde_json::Value> {
+    if line.is_empty() {
+        return None;
+    }
+    if line.contains("__SYSCORE_EVENT__") {
+        line.split("__SYSCORE_EVENT__")
+            .nth(1)
+            .and_then(|json_str| serde_json::from_str::<serde_json::Value>(json_str).ok())
+    } else {
+        Some(serde_json::json!({
+            "type": "Stdout",
+            "content": line
+        }))
+    }
+}
@@ -1 +1,2 @@
 pub mod manager;
+pub mod engine;
@@ -0,0 +1,148 @@
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, broadcast};
+
+const CHANNEL_CAPACITY: usize = 1024;
+const DEFAULT_REPLAY_CAPACITY: usize = 10_000;
+/// How long a finished job's buffer stays around for late or reconnecting subscribers
+const REPLAY_LINGER: Duration = Duration::from_secs(300);
+
+/// One live event as sent to subscribers: its serialized JSON and its
+/// position in the job's stream (0 for the first event).
+#[derive(Clone, Debug)]
+pub struct StreamEvent {
+    pub seq: u64,
+    pub data: String,
+}
+
+/// A consistent view of a job's stream from some seq on: the buffered
+/// events, then live ones with no gap or overlap between them.
+pub struct Subscription {
+    pub backlog: Vec<StreamEvent>,
+    /// `None` once the job finished; the backlog is then all there is
+    pub live: Option<broadcast::Receiver<StreamEvent>>,
+    /// Oldest seq still buffered. Past `from_seq` when earlier events were dropped.
+    pub first_seq: u64,
+}
+
+struct JobChannel {
+    // Dropped when the job ends so receivers see the stream close
+    sender: Option<broadcast::Sender<StreamEvent>>,
+    next_seq: u64,
+    buffer: VecDeque<StreamEvent>,
+    closed_at: Option<Instant>,
+}
+
+impl JobChannel {
+    fn new() -> Self {
+        Self {
+            sender: Some(broadcast::channel(CHANNEL_CAPACITY).0),
+            next_seq: 0,
+            buffer: VecDeque::new(),
+            closed_at: None,
+        }
+    }
+}
+
+/// Per-job broadcast channels carrying live execution events (serialized JSON).
+/// Shared by the local executor and the cluster coordinator so `/ws/stream`
+/// works regardless of where the job actually runs.
+///
+/// The last `SYSCORE_STREAM_REPLAY` events of every job (default 10000) are
+/// kept so subscribers can start or resume from any seq still buffered, also
+/// for a few minutes after the job finished.
+#[derive(Clone)]
+pub struct JobEvents {
+    channels: Arc<Mutex<HashMap<String, JobChannel>>>,
+    replay_capacity: usize,
+}
+
+impl JobEvents {
+    pub fn new() -> Self {
+        let replay_capacity = env::var("SYSCORE_STREAM_REPLAY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_REPLAY_CAPACITY);
+        Self {
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            replay_capacity,
+        }
+    }
+
+    /// Creates the channel for a job (or returns the existing one). A job run
+    /// again after its stream closed starts a fresh one.
+    pub async fn open(&self, job_id: &str) -> broadcast::Sender<StreamEvent> {
+        let mut channels = self.channels.lock().await;
+        let channel = channels.entry(job_id.to_string()).or_insert_with(JobChannel::new);
+        match &channel.sender {
+            Some(sender) => sender.clone(),
+            None => {
+                *channel = JobChannel::new();
+                channel.sender.clone().expect("new channel has a sender")
+            }
+        }
+    }
+
+    /// Buffered events from `from_seq` on plus a receiver for what follows.
+    /// Both are taken under the same lock `publish` holds, so nothing falls
+    /// between them.
+    pub async fn subscribe(&self, job_id: &str, from_seq: u64) -> Option<Subscription> {
+        let channels = self.channels.lock().await;
+        let channel = channels.get(job_id)?;
+        let first_seq = channel.buffer.front().map_or(channel.next_seq, |e| e.seq);
+        Some(Subscription {
+            backlog: channel.buffer.iter().filter(|e| e.seq >= from_seq).cloned().collect(),
+            live: channel.sender.as_ref().map(|sender| sender.subscribe()),
+            first_seq,
+        })
+    }
+
+    pub async fn publish(&self, job_id: &str, event: &Value) {
+        let mut channels = self.channels.lock().await;
+        let Some(channel) = channels.get_mut(job_id) else { return };
+        let Some(sender) = &channel.sender else { return };
+
+        let event = StreamEvent { seq: channel.next_seq, data: event.to_string() };
+        channel.next_seq += 1;
+        // No receivers is fine, nobody is watching this job
+        let _ = sender.send(event.clone());
+        if self.replay_capacity > 0 {
+            if channel.buffer.len() >= self.replay_capacity {
+                channel.buffer.pop_front();
+            }
+            channel.buffer.push_back(event);
+        }
+    }
+
+    /// Drops the job's channel and replay buffer right away, e.g. when its trace
+    /// is taken down. Live subscribers see the stream close.
+    pub async fn purge(&self, job_id: &str) {
+        self.channels.lock().await.remove(job_id);
+    }
+
+    /// Ends the stream; subscribers see it close once they drain it. The
+    /// buffer stays for `REPLAY_LINGER`. Closing a closed stream does nothing.
+    pub async fn close(&self, job_id: &str) {
+        let mut channels = self.channels.lock().await;
+        let Some(channel) = channels.get_mut(job_id) else { return };
+        if channel.sender.take().is_none() {
+            return;
+        }
+        channel.closed_at = Some(Instant::now());
+        drop(channels);
+
+        let events = self.clone();
+        let job_id = job_id.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(REPLAY_LINGER).await;
+            let mut channels = events.channels.lock().await;
+            // Unless the job was opened again in the meantime
+            if channels.get(&job_id).and_then(|c| c.closed_at).is_some_and(|at| at.elapsed() >= REPLAY_LINGER) {
+                channels.remove(&job_id);
+            }
+        });
+    }
+}
@@ -0,0 +1,358 @@
+use crate::docker::manager::{JobLimits, Language};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex, Notify};
+use uuid::Uuid;
+
+pub const JOBS_FILE: &str = "storage/jobs/jobs.jsonl";
+/// Finished jobs beyond this many are dropped from the registry, oldest first
+const MAX_FINISHED_JOBS: usize = 10_000;
+/// The file is compacted once it holds this many lines per live job...
+const COMPACT_LINES_PER_JOB: usize = 8;
+/// ...plus this many, so a near-empty registry isn't rewritten all the time
+const COMPACT_SLACK_LINES: usize = 1_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Uploading,
+    /// Ran fine but the trace store was unreachable; the trace sits in the spool
+    UploadPending,
+    Completed,
+    Failed,
+    /// Stopped on request; whatever trace it produced up to then is kept
+    Cancelled,
+}
+
+impl JobState {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "queued" => Some(JobState::Queued),
+            "running" => Some(JobState::Running),
+            "uploading" => Some(JobState::Uploading),
+            "upload_pending" => Some(JobState::UploadPending),
+            "completed" => Some(JobState::Completed),
+            "failed" => Some(JobState::Failed),
+            "cancelled" => Some(JobState::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobState::Completed | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateTransition {
+    pub state: JobState,
+    pub at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobRecord {
+    pub id: String,
+    pub language: Language,
+    /// SHA-256 of the submitted source, so we can spot repeats without storing code
+    pub code_hash: String,
+    pub submitted_at: DateTime<Utc>,
+    pub state: JobState,
+    pub transitions: Vec<StateTransition>,
+    pub limits: Option<JobLimits>,
+    pub exit_code: Option<i64>,
+    pub error: Option<String>,
+    pub trace_location: Option<String>,
+    /// Worker that ran the job (coordinator mode only)
+    pub worker: Option<String>,
+    /// SHA-256 of the key that lets a client cancel the job or write to it.
+    /// Never persisted or served: after a restart the job is over anyway.
+    #[serde(skip)]
+    pub control_key_hash: Option<String>,
+}
+
+struct RegistryInner {
+    jobs: HashMap<String, JobRecord>,
+    writer: mpsc::UnboundedSender<FileOp>,
+    // Lines in the file since it was last compacted
+    lines: usize,
+}
+
+/// Work for the writer task, applied to the file in order.
+enum FileOp {
+    Append(String),
+    /// Replace the whole file with these lines
+    Rewrite(Vec<String>),
+}
+
+/// Local record of the jobs this instance has seen.
+///
+/// Persisted as an append-only JSON-lines file: every change appends the full
+/// record and the last line per id wins on load. Writes happen on a background
+/// task, off the async runtime. The file is compacted on startup and whenever it
+/// has grown well past one line per job, dropping the oldest finished jobs
+/// beyond `MAX_FINISHED_JOBS`.
+#[derive(Clone)]
+pub struct JobRegistry {
+    inner: Arc<Mutex<RegistryInner>>,
+    // Woken on every change, for `wait_settled`
+    changed: Arc<Notify>,
+}
+
+impl JobRegistry {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+
+        let mut jobs = HashMap::new();
+        if let Ok(file) = File::open(&path) {
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                // A torn last line after a crash is skipped, earlier lines still count
+                if let Ok(record) = serde_json::from_str::<JobRecord>(&line) {
+                    jobs.insert(record.id.clone(), record);
+                }
+            }
+        }
+
+        // Jobs that were in flight when we went down will never finish. Pending
+        // uploads are the exception, the spool picks them up again.
+        for record in jobs.values_mut() {
+            if !record.state.is_terminal() && record.state != JobState::UploadPending {
+                record.apply(JobState::Failed, Some("Interrupted by syscore restart".to_string()));
+            }
+        }
+
+        prune(&mut jobs);
+        let lines = snapshot(&jobs);
+        let file = rewrite(&path, &lines)?;
+
+        let (writer, ops) = mpsc::unbounded_channel();
+        tokio::spawn(write_file(path.clone(), file, ops));
+
+        tracing::info!("Job registry loaded {} jobs from {}", jobs.len(), path.display());
+        Ok(Self {
+            inner: Arc::new(Mutex::new(RegistryInner { jobs, writer, lines: lines.len() })),
+            changed: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Records a new queued job. Submitting an id that is already known is a no-op.
+    pub async fn submit(&self, job_id: &str, language: Language, code: &str) -> JobRecord {
+        let mut inner = self.inner.lock().await;
+        if let Some(existing) = inner.jobs.get(job_id) {
+            return existing.clone();
+        }
+
+        let now = Utc::now();
+        let record = JobRecord {
+            id: job_id.to_string(),
+            language,
+            code_hash: format!("{:x}", Sha256::digest(code.as_bytes())),
+            submitted_at: now,
+            state: JobState::Queued,
+            transitions: vec![StateTransition { state: JobState::Queued, at: now, detail: None }],
+            limits: None,
+            exit_code: None,
+            error: None,
+            trace_location: None,
+            worker: None,
+            control_key_hash: None,
+        };
+        inner.jobs.insert(record.id.clone(), record.clone());
+        inner.persist(&record);
+        record
+    }
+
+    pub async fn transition(&self, job_id: &str, state: JobState, detail: Option<String>) {
+        self.update(job_id, |r| r.apply(state, detail)).await;
+    }
+
+    /// Applies a change to a job and persists the result. Unknown ids are ignored.
+    pub async fn update(&self, job_id: &str, change: impl FnOnce(&mut JobRecord)) {
+        let mut inner = self.inner.lock().await;
+        let Some(record) = inner.jobs.get_mut(job_id) else { return };
+        change(record);
+        let record = record.clone();
+        inner.persist(&record);
+        drop(inner);
+        self.changed.notify_waiters();
+    }
+
+    /// Issues a fresh control key for a job and returns it; only its hash is kept.
+    pub async fn issue_control_key(&self, job_id: &str) -> Option<String> {
+        let key = Uuid::new_v4().simple().to_string();
+        let mut inner = self.inner.lock().await;
+        let record = inner.jobs.get_mut(job_id)?;
+        record.control_key_hash = Some(control_key_hash(&key));
+        Some(key)
+    }
+
+    /// Whether `key` is the control key issued for the job.
+    pub async fn may_control(&self, job_id: &str, key: &str) -> bool {
+        let inner = self.inner.lock().await;
+        inner.jobs.get(job_id)
+            .and_then(|r| r.control_key_hash.as_deref())
+            .is_some_and(|hash| hash == control_key_hash(key))
+    }
+
+    pub async fn get(&self, job_id: &str) -> Option<JobRecord> {
+        self.inner.lock().await.jobs.get(job_id).cloned()
+    }
+
+    /// Waits until the job's run is over (a terminal state, or `upload_pending`)
+    /// and returns its record; at `timeout`, returns it as it is.
+    pub async fn wait_settled(&self, job_id: &str, timeout: Duration) -> Option<JobRecord> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Registered before looking, so a change in between still wakes us
+            let changed = self.changed.notified();
+            let record = self.get(job_id).await?;
+            let settled = record.state.is_terminal() || record.state == JobState::UploadPending;
+            if settled || tokio::time::timeout_at(deadline, changed).await.is_err() {
+                return Some(record);
+            }
+        }
+    }
+
+    /// Most recent jobs first, optionally filtered by state and submission time.
+    pub async fn list(&self, state: Option<JobState>, since: Option<DateTime<Utc>>, limit: usize) -> Vec<JobRecord> {
+        let inner = self.inner.lock().await;
+        let mut jobs: Vec<JobRecord> = inner.jobs.values()
+            .filter(|r| state.is_none_or(|s| r.state == s))
+            .filter(|r| since.is_none_or(|t| r.submitted_at >= t))
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|r| std::cmp::Reverse(r.submitted_at));
+        jobs.truncate(limit);
+        jobs
+    }
+}
+
+impl RegistryInner {
+    fn persist(&mut self, record: &JobRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("[Job {}] Failed to persist job record: {}", record.id, e);
+                return;
+            }
+        };
+        let _ = self.writer.send(FileOp::Append(line));
+        self.lines += 1;
+
+        if self.lines > self.jobs.len() * COMPACT_LINES_PER_JOB + COMPACT_SLACK_LINES {
+            prune(&mut self.jobs);
+            let lines = snapshot(&self.jobs);
+            self.lines = lines.len();
+            let _ = self.writer.send(FileOp::Rewrite(lines));
+        }
+    }
+}
+
+/// Drops the oldest finished jobs beyond `MAX_FINISHED_JOBS`.
+fn prune(jobs: &mut HashMap<String, JobRecord>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = jobs.values()
+        .filter(|r| r.state.is_terminal())
+        .map(|r| (r.submitted_at, r.id.clone()))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    let excess = finished.len() - MAX_FINISHED_JOBS;
+    for (_, id) in finished.into_iter().take(excess) {
+        jobs.remove(&id);
+    }
+    tracing::info!("Job registry pruned {} old jobs", excess);
+}
+
+/// One line per job, as written by a compaction.
+fn snapshot(jobs: &HashMap<String, JobRecord>) -> Vec<String> {
+    jobs.values().filter_map(|r| serde_json::to_string(r).ok()).collect()
+}
+
+/// Writes `lines` to a temporary file, swaps it in and reopens it for appending.
+fn rewrite(path: &Path, lines: &[String]) -> Result<File, String> {
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut out = File::create(&tmp).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        for line in lines {
+            writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+        }
+        out.sync_all().map_err(|e| e.to_string())?;
+    }
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+/// Applies queued file operations on the blocking pool, a batch at a time, so
+/// registry updates never wait on the disk.
+async fn write_file(path: PathBuf, file: File, mut ops: mpsc::UnboundedReceiver<FileOp>) {
+    let mut file = Some(file);
+    while let Some(op) = ops.recv().await {
+        let mut batch = vec![op];
+        while let Ok(op) = ops.try_recv() {
+            batch.push(op);
+        }
+
+        let path = path.clone();
+        let current = file.take();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut current = current;
+            for op in batch {
+                match op {
+                    FileOp::Append(line) => {
+                        if let Some(f) = current.as_mut() {
+                            if let Err(e) = writeln!(f, "{}", line) {
+                                tracing::error!("Failed to persist job record: {}", e);
+                            }
+                        }
+                    }
+                    FileOp::Rewrite(lines) => match rewrite(&path, &lines) {
+                        Ok(f) => current = Some(f),
+                        Err(e) => tracing::error!("Failed to compact job registry: {}", e),
+                    },
+                }
+            }
+            current
+        }).await;
+
+        match result {
+            Ok(current) => file = current,
+            Err(e) => {
+                tracing::error!("Job registry writer failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn control_key_hash(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+impl JobRecord {
+    fn apply(&mut self, state: JobState, detail: Option<String>) {
+        if state == JobState::Failed {
+            self.error = detail.clone();
+        }
+        self.state = state;
+        self.transitions.push(StateTransition { state, at: Utc::now(), detail });
+    }
+}
@@ -1,17 +1,36 @@
+mod analysis;
+mod cluster;
 mod docker;
+mod jobs;
 mod server;
 mod profiler;
 mod simulation;
 mod vm;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use std::net::SocketAddr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use crate::cluster::{ClusterConfig, ClusterMode};
+use crate::cluster::coordinator::Coordinator;
+use crate::cluster::worker::Worker;
+use crate::docker::engine::EngineConfig;
 use crate::docker::manager::ContainerManager;
-use crate::server::routes::{execute_handler, simulate_tick_handler, vm_malloc_handler, vm_write_handler, vm_reset_handler, vm_fs_handler};
+use crate::jobs::events::JobEvents;
+use crate::jobs::registry::{JobRegistry, JOBS_FILE};
+use crate::server::cluster::{register_handler, list_workers_handler, heartbeat_handler, poll_handler, job_events_handler, job_stdin_handler, job_complete_handler};
+use crate::server::jobs::{get_job_handler, list_jobs_handler, stream_job_events_handler};
+use crate::server::admin::{delete_trace_handler, retention_report_handler, run_retention_handler};
+use crate::server::playback::playback_handler;
+use crate::server::state::AppState;
+use crate::server::terminal::{terminal_handler, Terminals};
+use crate::server::trace_store;
+use crate::server::trace_store::retention::{Retention, RetentionPolicy};
+use crate::server::trace_store::spool::{TraceSpool, SPOOL_DIR};
+use crate::server::traces::{diff_traces_handler, export_trace_handler, get_trace_events_handler, get_trace_handler, get_trace_manifest_handler, get_trace_summary_handler, query_trace_handler};
+use crate::server::routes::{execute_handler, simulate_tick_handler, simulate_run_handler, vm_malloc_handler, vm_write_handler, vm_reset_handler, vm_fs_handler};
 use crate::server::aether::{upload_handler, list_handlers, download_handler};
 use crate::server::websocket::websocket_handler;
 
@@ -35,31 +54,100 @@ async fn main() {
         tracing::error!("Failed to create storage/aether: {}", e);
     }
 
-    // Initialize Docker connection
-    let container_manager = match ContainerManager::new() {
-        Ok(cm) => {
-            tracing::info!("Docker connection established");
-            cm
-        },
+    let cluster_config = match ClusterConfig::from_env() {
+        Ok(cfg) => cfg,
         Err(e) => {
-            tracing::error!("Failed to connect to Docker: {}", e);
+            tracing::error!("Invalid cluster configuration: {}", e);
             std::process::exit(1);
         }
     };
+    tracing::info!("Cluster mode: {:?}", cluster_config.mode);
 
-    // Pre-flight check: Ensure Docker is actually running and usable
-    if let Err(e) = container_manager.health_check().await {
-        tracing::error!("CRITICAL: Docker health check failed. The execution engine cannot start.");
-        tracing::error!("Reason: {}", e);
-        tracing::error!("Please ensure Docker Desktop/Engine is running.");
-        std::process::exit(1); 
+    let events = JobEvents::new();
+    let jobs = match JobRegistry::open(JOBS_FILE) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            tracing::error!("Failed to open job registry: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let traces = match trace_store::from_env() {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::error!("Invalid trace store configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tracing::info!("Trace store: {}", traces.kind());
+    if cluster_config.mode != ClusterMode::Standalone && !traces.is_shared() {
+        tracing::error!(
+            "Cluster mode needs a trace store every node shares (SYSCORE_TRACE_STORE=supabase), not {}: \
+             workers store the traces the coordinator serves",
+            traces.kind()
+        );
+        std::process::exit(1);
+    }
+
+    let uploads = match TraceSpool::new(SPOOL_DIR, traces.clone(), jobs.clone()) {
+        Ok(spool) => spool,
+        Err(e) => {
+            tracing::error!("Failed to open trace spool: {}", e);
+            std::process::exit(1);
+        }
+    };
+    uploads.spawn_retry_loop();
+
+    let retention = match RetentionPolicy::from_env() {
+        Ok(policy) => Retention::new(traces.clone(), jobs.clone(), policy),
+        Err(e) => {
+            tracing::error!("Invalid trace retention configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if retention.policy().is_enabled() {
+        tracing::info!("Trace retention: {:?}", retention.policy());
+    }
+    retention.spawn();
+
+    // Coordinators only dispatch, every other mode runs containers itself
+    let container_manager = if cluster_config.mode == ClusterMode::Coordinator {
+        None
+    } else {
+        Some(connect_engine(events.clone(), jobs.clone(), uploads.clone()).await)
+    };
+
+    let coordinator = if cluster_config.mode == ClusterMode::Coordinator {
+        let coordinator = Coordinator::new(cluster_config.token.clone(), events.clone(), jobs.clone());
+        coordinator.spawn_reaper();
+        Some(coordinator)
+    } else {
+        None
+    };
+
+    if let (ClusterMode::Worker, Some(manager)) = (cluster_config.mode, &container_manager) {
+        tracing::info!("Joining coordinator at {}", cluster_config.coordinator_url);
+        Worker::new(cluster_config.clone(), manager.clone()).spawn();
     }
 
+    let state = AppState {
+        manager: container_manager,
+        coordinator,
+        events,
+        jobs,
+        traces,
+        uploads,
+        retention,
+        admin_token: std::env::var("SYSCORE_ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        terminals: Terminals::from_env(),
+    };
+
     // Build application with routes
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/execute", post(execute_handler))
         .route("/api/simulate/cpu/tick", post(simulate_tick_handler))
+        .route("/api/simulate/cpu/run", post(simulate_run_handler))
         .route("/api/vm/malloc", post(vm_malloc_handler))
         .route("/api/vm/write", post(vm_write_handler))
         .route("/api/vm/reset", post(vm_reset_handler))
@@ -67,7 +155,29 @@ async fn main() {
         .route("/api/vm/fs/create", post(vm_fs_handler))
         .route("/api/v1/aether", get(list_handlers).post(upload_handler))
         .route("/api/v1/aether/download", get(download_handler))
+        .route("/api/jobs", get(list_jobs_handler))
+        .route("/api/jobs/:job_id", get(get_job_handler))
+        .route("/api/jobs/:job_id/events", get(stream_job_events_handler))
+        .route("/api/traces/diff", get(diff_traces_handler))
+        .route("/api/traces/:job_id", get(get_trace_handler))
+        .route("/api/traces/:job_id/manifest", get(get_trace_manifest_handler))
+        .route("/api/traces/:job_id/summary", get(get_trace_summary_handler))
+        .route("/api/traces/:job_id/events", get(get_trace_events_handler))
+        .route("/api/traces/:job_id/query", get(query_trace_handler))
+        .route("/api/traces/:job_id/export", get(export_trace_handler))
+        .route("/api/admin/traces/retention", get(retention_report_handler).post(run_retention_handler))
+        .route("/api/admin/traces/:job_id", delete(delete_trace_handler))
         .route("/ws/stream", get(websocket_handler))
+        .route("/ws/terminal", get(terminal_handler))
+        .route("/ws/simulation", get(playback_handler))
+        .route("/api/cluster/workers", get(list_workers_handler).post(register_handler))
+        .route("/api/cluster/workers/:worker_id/heartbeat", post(heartbeat_handler))
+        .route("/api/cluster/workers/:worker_id/poll", post(poll_handler))
+        .route("/api/cluster/jobs/:job_id/events", post(job_events_handler))
+        .route("/api/cluster/jobs/:job_id/stdin", post(job_stdin_handler))
+        .route("/api/cluster/jobs/:job_id/complete", post(job_complete_handler))
+        // gzip for clients that send Accept-Encoding, trace pages shrink a lot
+        .layer(tower_http::compression::CompressionLayer::new())
         .layer(
             tower_http::cors::CorsLayer::new()
                 .allow_origin([
@@ -78,6 +188,7 @@ async fn main() {
                 .allow_methods([
                     axum::http::Method::GET,
                     axum::http::Method::POST,
+                    axum::http::Method::DELETE,
                     axum::http::Method::OPTIONS,
                 ])
                 .allow_headers([
@@ -85,18 +196,58 @@ async fn main() {
                     axum::http::header::AUTHORIZATION,
                 ]),
         )
-        .with_state(container_manager);
+        .with_state(state);
 
-    // Address to listen on
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
+    // Address to listen on (coordinators must be reachable by their workers)
+    let addr = match std::env::var("SYSCORE_LISTEN_ADDR") {
+        Ok(v) => v.parse::<SocketAddr>().unwrap_or_else(|_| {
+            tracing::error!("Invalid SYSCORE_LISTEN_ADDR: {}", v);
+            std::process::exit(1);
+        }),
+        Err(_) => SocketAddr::from(([127, 0, 0, 1], 3001)),
+    };
     tracing::info!("Listening on {}", addr);
 
     // Start server
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses let terminals be limited per client
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
 
 async fn health_check() -> String {
     let version = option_env!("SYSCORE_VERSION").unwrap_or("unknown");
     format!("SysCore Backend: ONLINE (Build: {})", version)
 }
+
+/// Connects to the container engine and verifies it, exiting the process on failure.
+async fn connect_engine(events: JobEvents, jobs: JobRegistry, uploads: TraceSpool) -> ContainerManager {
+    // Initialize engine connection (Docker or Podman, local or remote)
+    let engine_config = match EngineConfig::from_env() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            tracing::error!("Invalid container engine configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let container_manager = match ContainerManager::new(&engine_config, events, jobs, uploads) {
+        Ok(cm) => {
+            tracing::info!("Engine connection established ({})", engine_config.describe());
+            cm
+        },
+        Err(e) => {
+            tracing::error!("Failed to connect to container engine at {}: {}", engine_config.describe(), e);
+            std::process::exit(1);
+        }
+    };
+
+    // Pre-flight check: Ensure the engine is actually running and usable
+    if let Err(e) = container_manager.health_check().await {
+        tracing::error!("CRITICAL: Container engine health check failed. The execution engine cannot start.");
+        tracing::error!("Reason: {}", e);
+        tracing::error!("Please ensure Docker Desktop/Engine (or the Podman socket) is running and reachable at {}.", engine_config.describe());
+        std::process::exit(1); 
+    }
+
+    container_manager
+}
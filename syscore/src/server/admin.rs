@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Serialize;
+use crate::jobs::registry::JobState;
+use super::state::AppState;
+use super::trace_store::retention::{PurgeReport, RetentionPolicy};
+use super::trace_store::validate_job_id;
+
+#[derive(Serialize)]
+pub struct RetentionStatus {
+    pub policy: RetentionPolicy,
+    pub last_report: Option<PurgeReport>,
+}
+
+#[derive(Serialize)]
+pub struct TraceDeletion {
+    pub job_id: String,
+    /// Whether an upload was still waiting in the spool
+    pub discarded_spooled: bool,
+}
+
+/// Checks the `SYSCORE_ADMIN_TOKEN` bearer token.
+pub(super) fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let token = state.admin_token.as_deref()
+        .ok_or((StatusCode::FORBIDDEN, "Admin API is disabled (SYSCORE_ADMIN_TOKEN not set)".to_string()))?;
+
+    let bearer = headers.get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    if bearer != Some(token) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid or missing admin token".to_string()));
+    }
+    Ok(())
+}
+
+/// Deletes a job's trace everywhere syscore may hold it, for takedown requests.
+pub async fn delete_trace_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<TraceDeletion>, (StatusCode, String)> {
+    authorize(&state, &headers)?;
+    validate_job_id(&job_id).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    // Spool first, or a pending retry could upload the trace again. This waits
+    // for a retry already uploading, whose copy the store delete then removes.
+    let discarded_spooled = state.uploads.discard(&job_id).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.traces.delete(&job_id).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.jobs.update(&job_id, |r| r.trace_location = None).await;
+    // Resumed stream subscribers must not get the events either
+    state.events.purge(&job_id).await;
+    if discarded_spooled {
+        state.jobs.transition(&job_id, JobState::Completed, Some("trace deleted before upload".to_string())).await;
+    }
+
+    tracing::info!("[Job {}] Trace deleted on admin request", job_id);
+    Ok(Json(TraceDeletion { job_id, discarded_spooled }))
+}
+
+pub async fn retention_report_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<RetentionStatus>, (StatusCode, String)> {
+    authorize(&state, &headers)?;
+    Ok(Json(RetentionStatus {
+        policy: state.retention.policy().clone(),
+        last_report: state.retention.last_report().await,
+    }))
+}
+
+/// Runs the retention policy right away instead of waiting for the next cycle.
+pub async fn run_retention_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<PurgeReport>, (StatusCode, String)> {
+    authorize(&state, &headers)?;
+    Ok(Json(state.retention.run_once().await))
+}
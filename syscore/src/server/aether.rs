@@ -1,17 +1,15 @@
 use axum::{
-    extract::{Multipart, Path, Query, State},
+    extract::{Multipart, Query},
     http::{StatusCode, HeaderMap},
-    response::{IntoResponse, Response},
+    response::IntoResponse,
     Json,
     body::Body,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fs;
-use std::path::{PathBuf, Component};
+use std::path::PathBuf;
 use tokio::fs as tokio_fs;
-use tokio::io::AsyncWriteExt;
-use tracing::{info, error};
+use tracing::info;
 
 const STORAGE_DIR: &str = "storage/aether";
 
@@ -0,0 +1,108 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use crate::cluster::coordinator::Coordinator;
+use crate::cluster::{EventBatch, HeartbeatResponse, JobCompletion, RegisterRequest, StdinPoll};
+use super::state::AppState;
+
+/// Resolves the coordinator and checks the worker's bearer token.
+fn authorized(state: &AppState, headers: &HeaderMap) -> Result<Coordinator, (StatusCode, String)> {
+    let coordinator = state.coordinator.clone()
+        .ok_or((StatusCode::NOT_FOUND, "This instance is not a coordinator".to_string()))?;
+
+    let bearer = headers.get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    if !coordinator.authorize(bearer) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid or missing cluster token".to_string()));
+    }
+    Ok(coordinator)
+}
+
+pub async fn register_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<RegisterRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    Ok(Json(coordinator.register(payload).await))
+}
+
+pub async fn list_workers_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    Ok(Json(coordinator.workers().await))
+}
+
+pub async fn heartbeat_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(worker_id): Path<String>,
+) -> Result<Json<HeartbeatResponse>, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    match coordinator.heartbeat(&worker_id).await {
+        Some(cancel) => Ok(Json(HeartbeatResponse { cancel })),
+        None => Err((StatusCode::NOT_FOUND, "Unknown worker".to_string())),
+    }
+}
+
+pub async fn poll_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(worker_id): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    match coordinator.poll(&worker_id).await {
+        Ok(Some(job)) => Ok(Json(job).into_response()),
+        Ok(None) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(()) => Err((StatusCode::NOT_FOUND, "Unknown worker".to_string())),
+    }
+}
+
+pub async fn job_events_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Json(payload): Json<EventBatch>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    if coordinator.publish(&payload.worker_id, &job_id, &payload.events).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::CONFLICT, "Job is not assigned to this worker".to_string()))
+    }
+}
+
+pub async fn job_stdin_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Json(payload): Json<StdinPoll>,
+) -> Result<Response, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    match coordinator.poll_stdin(&payload.worker_id, &job_id).await {
+        Ok(input) if input.is_empty() => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(input) => Ok(Json(input).into_response()),
+        Err(()) => Err((StatusCode::CONFLICT, "Job is not assigned to this worker".to_string())),
+    }
+}
+
+pub async fn job_complete_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Json(payload): Json<JobCompletion>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let coordinator = authorized(&state, &headers)?;
+    if coordinator.complete(&payload.worker_id, &job_id, payload.result, payload.record).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::CONFLICT, "Job is not assigned to this worker".to_string()))
+    }
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use crate::jobs::registry::{JobRecord, JobState};
+use super::admin::authorize;
+use super::state::AppState;
+use super::websocket::{forward, open_stream, ServerFrame};
+
+const DEFAULT_LIST_LIMIT: usize = 100;
+const MAX_LIST_LIMIT: usize = 1000;
+/// Frames buffered for a slow SSE client, as for a websocket
+const SSE_CAPACITY: usize = 256;
+/// How long `done` waits for the job's final state after its stream ended
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+pub struct EventStreamQuery {
+    /// Where to start when there is no `Last-Event-ID`
+    pub from_seq: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct JobListQuery {
+    pub state: Option<String>,
+    /// RFC 3339 timestamp, only jobs submitted at or after it are returned
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+pub async fn get_job_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobRecord>, (StatusCode, String)> {
+    state.jobs.get(&job_id).await
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("Job {} not found", job_id)))
+}
+
+/// Lists jobs across every client, so only with the admin token.
+pub async fn list_jobs_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<JobListQuery>,
+) -> Result<Json<Vec<JobRecord>>, (StatusCode, String)> {
+    authorize(&state, &headers)?;
+    let job_state = match params.state.as_deref() {
+        None | Some("") => None,
+        Some(s) => Some(JobState::parse(s).ok_or((StatusCode::BAD_REQUEST, format!("Unknown state: {}", s)))?),
+    };
+
+    let since = match params.since.as_deref() {
+        None | Some("") => None,
+        Some(s) => Some(
+            DateTime::parse_from_rfc3339(s)
+                .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid since timestamp: {}", s)))?
+                .with_timezone(&Utc),
+        ),
+    };
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT).min(MAX_LIST_LIMIT);
+    Ok(Json(state.jobs.list(job_state, since, limit).await))
+}
+
+/// A job's live events as Server-Sent Events, for clients that can't use
+/// `/ws/stream`. Frames are the websocket's, with the seq as the event id so
+/// a reconnecting client resumes through `Last-Event-ID`. Ends with a `done`
+/// event carrying the job's final state.
+pub async fn stream_job_events_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<EventStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    if state.jobs.get(&job_id).await.is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("Job {} not found", job_id)));
+    }
+    let last_event_id = headers.get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|id| id.trim().parse::<u64>().ok());
+    let from_seq = last_event_id.map(|seq| seq + 1).or(params.from_seq).unwrap_or(0);
+
+    let (out, frames) = mpsc::channel(SSE_CAPACITY);
+    match open_stream(&state, &job_id, from_seq).await {
+        Ok(subscription) => {
+            tokio::spawn(forward(state.events.clone(), job_id.clone(), from_seq, subscription, out));
+        }
+        // Finished and no longer buffered: there is only the outcome left to tell
+        Err(e) => {
+            let _ = out.try_send(ServerFrame::Error { id: None, job_id: Some(job_id.clone()), message: e });
+        }
+    }
+
+    let events = stream::unfold(frames, |mut frames| async move {
+        frames.recv().await.map(|frame| (frame, frames))
+    }).filter_map(|frame| async move { sse_event(&frame).map(Ok) });
+
+    let done = stream::once(async move {
+        let record = state.jobs.wait_settled(&job_id, SETTLE_TIMEOUT).await;
+        let data = serde_json::json!({
+            "type": "done",
+            "job_id": job_id,
+            "state": record.as_ref().map(|r| r.state),
+            "error": record.and_then(|r| r.error),
+        });
+        Ok(Event::default().event("done").data(data.to_string()))
+    });
+
+    Ok(Sse::new(events.chain(done)).keep_alive(KeepAlive::default()))
+}
+
+/// `end` is left out, `done` follows it.
+fn sse_event(frame: &ServerFrame) -> Option<Event> {
+    let event = match frame {
+        ServerFrame::Event { seq, .. } => Event::default().event("event").id(seq.to_string()),
+        ServerFrame::Error { .. } => Event::default().event("error"),
+        _ => return None,
+    };
+    Some(event.data(serde_json::to_string(frame).ok()?))
+}
@@ -2,3 +2,10 @@ pub mod routes;
 pub mod websocket;
 pub mod trace_store;
 pub mod aether;
+pub mod admin;
+pub mod cluster;
+pub mod jobs;
+pub mod playback;
+pub mod state;
+pub mod terminal;
+pub mod traces;
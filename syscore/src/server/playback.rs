@@ -0,0 +1,203 @@
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::simulation::{next_tick, Process, ProcessState, SimulationState};
+
+/// Bounds for `speed`, the milliseconds between two ticks
+const MIN_SPEED_MS: i32 = 10;
+const MAX_SPEED_MS: i32 = 60_000;
+
+/// What a client sends on `/ws/simulation`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PlaybackCommand {
+    /// Replaces the simulation; plays right away if `isPlaying` is set
+    Load { state: Box<SimulationState> },
+    Play,
+    Pause,
+    /// Advances one tick, also while paused
+    Step,
+    Speed { speed: i32 },
+    AddProcess { process: NewProcess },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewProcess {
+    /// Defaults to one past the highest id
+    id: Option<i32>,
+    name: Option<String>,
+    burst_time: i32,
+    #[serde(default)]
+    arrival_time: i32,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    color: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PlaybackFrame {
+    /// The whole simulation, after a command
+    State { state: Box<SimulationState> },
+    /// What one tick changed, see `diff_states`
+    Tick { changes: Map<String, Value> },
+    /// Every process completed, playback stopped
+    #[serde(rename_all = "camelCase")]
+    Finished { current_time: i32 },
+    Error { message: String },
+}
+
+pub async fn playback_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(run_playback)
+}
+
+/// Holds one simulation per connection and advances it with `next_tick`
+/// every `speed` ms while playing.
+async fn run_playback(mut socket: WebSocket) {
+    let mut state: Option<SimulationState> = None;
+    let mut next_at = Instant::now();
+
+    loop {
+        let playing = state.as_ref().is_some_and(|s| s.is_playing);
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    _ => continue, // axum answers protocol pings itself
+                };
+                let (frames, reschedule) = match serde_json::from_str::<PlaybackCommand>(&text) {
+                    Ok(command) => {
+                        let reschedule = matches!(command, PlaybackCommand::Load { .. } | PlaybackCommand::Play | PlaybackCommand::Speed { .. });
+                        (handle(&mut state, command), reschedule)
+                    }
+                    Err(e) => (Err(format!("Invalid message: {}", e)), false),
+                };
+                let frames = frames.unwrap_or_else(|message| vec![PlaybackFrame::Error { message }]);
+                for frame in frames {
+                    if !send_frame(&mut socket, &frame).await {
+                        return;
+                    }
+                }
+                // The next tick is a full interval after play or a speed change
+                if let (true, Some(sim)) = (reschedule, &state) {
+                    next_at = Instant::now() + tick_interval(sim);
+                }
+            }
+            _ = tokio::time::sleep_until(next_at), if playing => {
+                let Some(sim) = state.as_mut() else { continue };
+                next_at += tick_interval(sim);
+                let frames = match tick(sim) {
+                    Ok(frames) => frames,
+                    Err(message) => vec![PlaybackFrame::Error { message }],
+                };
+                for frame in frames {
+                    if !send_frame(&mut socket, &frame).await {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Applies a command. `step` answers with the tick's diff, everything else
+/// with the full state.
+fn handle(state: &mut Option<SimulationState>, command: PlaybackCommand) -> Result<Vec<PlaybackFrame>, String> {
+    if let PlaybackCommand::Load { state: loaded } = command {
+        loaded.validate()?;
+        let sim = state.insert(*loaded);
+        sim.is_playing &= !is_finished(sim);
+        return Ok(vec![PlaybackFrame::State { state: Box::new(sim.clone()) }]);
+    }
+    let sim = state.as_mut().ok_or("No simulation loaded")?;
+    match command {
+        PlaybackCommand::Step => return tick(sim),
+        PlaybackCommand::Play if is_finished(sim) => return Err("Every process has completed".to_string()),
+        PlaybackCommand::Play => sim.is_playing = true,
+        PlaybackCommand::Pause => sim.is_playing = false,
+        PlaybackCommand::Speed { speed } => sim.speed = speed.clamp(MIN_SPEED_MS, MAX_SPEED_MS),
+        PlaybackCommand::AddProcess { process } => {
+            let id = process.id.unwrap_or_else(|| sim.processes.iter().map(|p| p.id).max().unwrap_or(0) + 1);
+            if sim.processes.iter().any(|p| p.id == id) {
+                return Err(format!("Process {} already exists", id));
+            }
+            if process.burst_time <= 0 {
+                return Err("burstTime must be positive".to_string());
+            }
+            let name = process.name.unwrap_or_else(|| format!("P{}", id));
+            sim.processes.push(Process::new(id, name, process.burst_time, process.arrival_time, process.priority, process.color));
+        }
+        PlaybackCommand::Load { .. } => unreachable!("handled above"),
+    }
+    Ok(vec![PlaybackFrame::State { state: Box::new(sim.clone()) }])
+}
+
+/// Advances one tick and returns what to send: the diff, plus `finished`
+/// when that was the last process completing.
+fn tick(sim: &mut SimulationState) -> Result<Vec<PlaybackFrame>, String> {
+    if is_finished(sim) {
+        sim.is_playing = false;
+        return Err("Every process has completed".to_string());
+    }
+    let before = serde_json::to_value(&*sim).map_err(|e| e.to_string())?;
+    *sim = next_tick(sim.clone());
+    if is_finished(sim) {
+        sim.is_playing = false;
+    }
+    let after = serde_json::to_value(&*sim).map_err(|e| e.to_string())?;
+
+    let mut frames = vec![PlaybackFrame::Tick { changes: diff_states(&before, &after) }];
+    if is_finished(sim) {
+        frames.push(PlaybackFrame::Finished { current_time: sim.current_time });
+    }
+    Ok(frames)
+}
+
+fn is_finished(sim: &SimulationState) -> bool {
+    sim.processes.iter().all(|p| p.state == ProcessState::Completed)
+}
+
+fn tick_interval(sim: &SimulationState) -> Duration {
+    Duration::from_millis(sim.speed.clamp(MIN_SPEED_MS, MAX_SPEED_MS) as u64)
+}
+
+/// The top-level fields of the state that changed. `processes` holds only
+/// the processes that changed (matched by `id`), and `ganttChart` is
+/// `{"from": i, "blocks": [...]}`: the chart from block `i` on, since a tick
+/// only extends the last block or appends new ones.
+fn diff_states(before: &Value, after: &Value) -> Map<String, Value> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else { return Map::new() };
+    let mut changes = Map::new();
+    for (key, value) in after {
+        let old = before.get(key);
+        if old == Some(value) {
+            continue;
+        }
+        let change = match (key.as_str(), old.and_then(Value::as_array), value.as_array()) {
+            ("processes", Some(old), Some(new)) => {
+                Value::Array(new.iter().filter(|p| !old.contains(p)).cloned().collect())
+            }
+            ("ganttChart", Some(old), Some(new)) => {
+                let from = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+                serde_json::json!({ "from": from, "blocks": &new[from..] })
+            }
+            _ => value.clone(),
+        };
+        changes.insert(key.clone(), change);
+    }
+    changes
+}
+
+/// Returns false once the socket is gone.
+async fn send_frame(socket: &mut WebSocket, frame: &PlaybackFrame) -> bool {
+    let Ok(text) = serde_json::to_string(frame) else { return true };
+    socket.send(Message::Text(text)).await.is_ok()
+}
@@ -1,52 +1,146 @@
-use axum::{Json, extract::State};
+use axum::{Json, extract::State, http::StatusCode};
 use serde::{Deserialize, Serialize};
-use crate::docker::manager::{ContainerManager, Language};
-use crate::simulation::{SimulationState, next_tick};
+use crate::docker::manager::{Language, RunOptions, StdinInput};
+use crate::simulation::{SimulationState, next_tick, run_to_completion, SimulationReport, SimulationRunRequest};
+use crate::simulation::run::{DEFAULT_MAX_TICKS, MAX_PROCESSES, MAX_TICKS_LIMIT};
 use crate::vm::{VMState, VMMallocRequest, VMWriteRequest, FSOperationRequest, FSOperationResponse};
-use super::aether; // Import local module from parent server module if needed, or crate::server::aether
+use super::state::AppState;
+use uuid::Uuid;
 
 #[derive(Deserialize)]
 pub struct ExecuteRequest {
     pub language: String,
     pub code: String,
+    /// Return the job id right away and run in the background (poll `/api/jobs/{id}`)
+    #[serde(default)]
+    pub detach: bool,
+    #[serde(flatten)]
+    pub options: RunOptions,
 }
 
 #[derive(Serialize)]
 pub struct ExecuteResponse {
     pub status: String,
     pub output: String,
+    /// Detached runs only: the key `/ws/stream` wants to cancel the job or write to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_key: Option<String>,
 }
 
 pub async fn execute_handler(
-    State(manager): State<ContainerManager>,
+    State(state): State<AppState>,
     Json(payload): Json<ExecuteRequest>,
 ) -> Json<ExecuteResponse> {
-    let lang = match payload.language.as_str() {
-        "python" => Language::Python,
-        "cpp" | "c++" => Language::Cpp,
-        _ => return Json(ExecuteResponse {
+    let lang = match Language::parse(&payload.language) {
+        Some(lang) => lang,
+        None => return Json(ExecuteResponse {
             status: "error".to_string(),
             output: "Unsupported language".to_string(),
+            control_key: None,
         }),
     };
 
-    match manager.execute(lang, payload.code).await {
+    let job_id = Uuid::new_v4().to_string();
+
+    if payload.detach {
+        // Record it now so a poll right after this response already finds the job
+        state.jobs.submit(&job_id, lang.clone(), &payload.code).await;
+        // Subscribers can wait on the stream before the executor takes the job;
+        // `run_job` closes it whatever happens to the job
+        state.events.open(&job_id).await;
+        let control_key = state.jobs.issue_control_key(&job_id).await;
+        let id = job_id.clone();
+        tokio::spawn(async move {
+            let _ = run_job(&state, &id, lang, payload.code, payload.options).await;
+        });
+        return Json(ExecuteResponse {
+            status: "queued".to_string(),
+            output: job_id,
+            control_key,
+        });
+    }
+
+    match run_job(&state, &job_id, lang, payload.code, payload.options).await {
         Ok(result) => Json(ExecuteResponse {
             status: "success".to_string(),
             output: result,
+            control_key: None,
         }),
         Err(e) => Json(ExecuteResponse {
             status: "error".to_string(),
             output: e,
+            control_key: None,
         }),
     }
 }
 
+/// Cancels a job wherever `run_job` sent it.
+pub async fn cancel_job(state: &AppState, job_id: &str) -> Result<(), String> {
+    match (&state.coordinator, &state.manager) {
+        (Some(coordinator), _) => coordinator.cancel(job_id).await,
+        (None, Some(manager)) => manager.cancel(job_id).await,
+        (None, None) => Err("No execution backend available".to_string()),
+    }
+}
+
+/// Sends input to an interactive job wherever `run_job` sent it.
+pub async fn write_stdin(state: &AppState, job_id: &str, input: StdinInput) -> Result<(), String> {
+    match (&state.coordinator, &state.manager) {
+        (Some(coordinator), _) => coordinator.write_stdin(job_id, input).await,
+        (None, Some(manager)) => manager.write_stdin(job_id, input).await,
+        (None, None) => Err("No execution backend available".to_string()),
+    }
+}
+
+/// Coordinators hand the job to a worker, everything else runs it locally.
+async fn run_job(state: &AppState, job_id: &str, lang: Language, code: String, options: RunOptions) -> Result<String, String> {
+    let result = match (&state.coordinator, &state.manager) {
+        (Some(coordinator), _) => coordinator.execute(job_id, lang, code, options).await,
+        (None, Some(manager)) => manager.execute(job_id, lang, code, options).await,
+        (None, None) => Err("No execution backend available".to_string()),
+    };
+    // The executors close the stream themselves, unless they never got the job
+    state.events.close(job_id).await;
+    result
+}
+
 pub async fn simulate_tick_handler(
     Json(payload): Json<SimulationState>,
-) -> Json<SimulationState> {
+) -> Result<Json<SimulationState>, (StatusCode, String)> {
+    payload.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
     let next_state = next_tick(payload);
-    Json(next_state)
+    Ok(Json(next_state))
+}
+
+/// Runs a workload to completion and reports the schedule and its metrics.
+pub async fn simulate_run_handler(
+    Json(payload): Json<SimulationRunRequest>,
+) -> Result<Json<SimulationReport>, (StatusCode, String)> {
+    let state = payload.state;
+    state.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if state.processes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No processes to run".to_string()));
+    }
+    if state.processes.len() > MAX_PROCESSES {
+        return Err((StatusCode::BAD_REQUEST, format!("At most {} processes per run", MAX_PROCESSES)));
+    }
+    for (i, p) in state.processes.iter().enumerate() {
+        if state.processes[..i].iter().any(|other| other.id == p.id) {
+            return Err((StatusCode::BAD_REQUEST, format!("Duplicate process id {}", p.id)));
+        }
+        if p.bursts.is_empty() && p.burst_time <= 0 {
+            return Err((StatusCode::BAD_REQUEST, format!("Process {} needs a positive burstTime or bursts", p.id)));
+        }
+    }
+    let max_ticks = payload.max_ticks.unwrap_or(DEFAULT_MAX_TICKS);
+    if !(1..=MAX_TICKS_LIMIT).contains(&max_ticks) {
+        return Err((StatusCode::BAD_REQUEST, format!("maxTicks must be between 1 and {}", MAX_TICKS_LIMIT)));
+    }
+
+    let report = tokio::task::spawn_blocking(move || run_to_completion(state, max_ticks))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Simulation failed: {}", e)))?;
+    Ok(Json(report))
 }
 
 pub async fn vm_malloc_handler(
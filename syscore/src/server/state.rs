@@ -0,0 +1,26 @@
+use crate::cluster::coordinator::Coordinator;
+use crate::docker::manager::ContainerManager;
+use crate::jobs::events::JobEvents;
+use crate::jobs::registry::JobRegistry;
+use super::trace_store::retention::Retention;
+use super::trace_store::spool::TraceSpool;
+use super::terminal::Terminals;
+use super::trace_store::SharedTraceStore;
+
+/// Shared state for all routes.
+///
+/// `manager` is absent on a coordinator that has no local engine, and
+/// `coordinator` is only present in coordinator mode. The admin API is
+/// disabled while `admin_token` is unset.
+#[derive(Clone)]
+pub struct AppState {
+    pub manager: Option<ContainerManager>,
+    pub coordinator: Option<Coordinator>,
+    pub events: JobEvents,
+    pub jobs: JobRegistry,
+    pub traces: SharedTraceStore,
+    pub uploads: TraceSpool,
+    pub retention: Retention,
+    pub admin_token: Option<String>,
+    pub terminals: Terminals,
+}
@@ -0,0 +1,225 @@
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, ConnectInfo, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::docker::manager::{ContainerManager, Language, TerminalSession};
+use super::state::AppState;
+
+const DEFAULT_IDLE_SECS: u64 = 600;
+const DEFAULT_MAX_SECS: u64 = 3600;
+const DEFAULT_MAX_SESSIONS: usize = 8;
+const DEFAULT_MAX_PER_CLIENT: usize = 2;
+const DEFAULT_COLS: u16 = 80;
+const DEFAULT_ROWS: u16 = 24;
+
+/// Limits for browser terminals, read from `SYSCORE_TERMINAL_IDLE_SECS`,
+/// `SYSCORE_TERMINAL_MAX_SECS`, `SYSCORE_TERMINAL_MAX_SESSIONS` and
+/// `SYSCORE_TERMINAL_MAX_PER_CLIENT`.
+#[derive(Clone)]
+pub struct Terminals {
+    idle_timeout: Duration,
+    max_duration: Duration,
+    slots: Arc<Semaphore>,
+    max_per_client: usize,
+    // Open sessions per client address
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Terminals {
+    pub fn from_env() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(env_or("SYSCORE_TERMINAL_IDLE_SECS", DEFAULT_IDLE_SECS)),
+            max_duration: Duration::from_secs(env_or("SYSCORE_TERMINAL_MAX_SECS", DEFAULT_MAX_SECS)),
+            slots: Arc::new(Semaphore::new(env_or("SYSCORE_TERMINAL_MAX_SESSIONS", DEFAULT_MAX_SESSIONS))),
+            max_per_client: env_or("SYSCORE_TERMINAL_MAX_PER_CLIENT", DEFAULT_MAX_PER_CLIENT),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts a session against `client`, unless it has its share open already.
+    fn claim(&self, client: IpAddr) -> Option<ClientSlot> {
+        let mut clients = self.clients.lock().unwrap();
+        let open = clients.entry(client).or_insert(0);
+        if *open >= self.max_per_client {
+            return None;
+        }
+        *open += 1;
+        Some(ClientSlot { clients: self.clients.clone(), client })
+    }
+}
+
+/// One client's claim on a session, given back when the session ends.
+struct ClientSlot {
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    client: IpAddr,
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(open) = clients.get_mut(&self.client) {
+            *open -= 1;
+            if *open == 0 {
+                clients.remove(&self.client);
+            }
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[derive(Deserialize)]
+pub struct TerminalParams {
+    language: String,
+    cols: Option<u16>,
+    rows: Option<u16>,
+}
+
+/// Control messages from the client. Keystrokes may also come as binary frames.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TerminalInput {
+    Input { data: String },
+    Resize { cols: u16, rows: u16 },
+}
+
+/// Control messages to the client; terminal output goes out as binary frames.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TerminalFrame {
+    Ready,
+    Exit { reason: ExitReason },
+    Error { message: String },
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ExitReason {
+    /// The shell exited
+    Exited,
+    /// The client went away
+    Closed,
+    IdleTimeout,
+    MaxDuration,
+}
+
+pub async fn terminal_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Query(params): Query<TerminalParams>,
+) -> Result<Response, (StatusCode, String)> {
+    let lang = Language::parse(&params.language)
+        .ok_or((StatusCode::BAD_REQUEST, format!("Unsupported language: {}", params.language)))?;
+    let manager = state.manager.clone()
+        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Terminals need a local container engine".to_string()))?;
+    let client = state.terminals.claim(peer.ip())
+        .ok_or((StatusCode::TOO_MANY_REQUESTS, "Too many terminal sessions from this client".to_string()))?;
+    let permit = state.terminals.slots.clone().try_acquire_owned()
+        .map_err(|_| (StatusCode::SERVICE_UNAVAILABLE, "Too many terminal sessions".to_string()))?;
+
+    let size = (params.cols.unwrap_or(DEFAULT_COLS), params.rows.unwrap_or(DEFAULT_ROWS));
+    let terminals = state.terminals.clone();
+    Ok(ws.on_upgrade(move |socket| run_session(socket, manager, terminals, lang, size, (permit, client))).into_response())
+}
+
+async fn run_session(
+    mut socket: WebSocket,
+    manager: ContainerManager,
+    terminals: Terminals,
+    lang: Language,
+    (cols, rows): (u16, u16),
+    _slots: (OwnedSemaphorePermit, ClientSlot),
+) {
+    let mut session = match manager.start_terminal(lang, cols, rows).await {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::error!("Terminal session failed to start: {}", e);
+            let _ = send_frame(&mut socket, &TerminalFrame::Error { message: e }).await;
+            return;
+        }
+    };
+
+    let reason = if send_frame(&mut socket, &TerminalFrame::Ready).await {
+        bridge(&mut socket, &manager, &mut session, &terminals).await
+    } else {
+        ExitReason::Closed
+    };
+    tracing::info!("Terminal session in container {} ended: {:?}", session.container, reason);
+
+    let _ = send_frame(&mut socket, &TerminalFrame::Exit { reason }).await;
+    let _ = socket.close().await;
+    manager.end_terminal(session).await;
+}
+
+/// Pumps output to the socket and input to the shell until one side ends
+/// or a limit is hit. Only input counts as activity for the idle timeout.
+async fn bridge(socket: &mut WebSocket, manager: &ContainerManager, session: &mut TerminalSession, terminals: &Terminals) -> ExitReason {
+    let deadline = tokio::time::sleep(terminals.max_duration);
+    let idle = tokio::time::sleep(terminals.idle_timeout);
+    tokio::pin!(deadline, idle);
+
+    loop {
+        tokio::select! {
+            output = session.output.next() => match output {
+                Some(Ok(chunk)) => {
+                    if socket.send(Message::Binary(chunk.into_bytes().to_vec())).await.is_err() {
+                        return ExitReason::Closed;
+                    }
+                }
+                Some(Err(e)) => {
+                    tracing::debug!("Terminal output from {} failed: {}", session.container, e);
+                    return ExitReason::Exited;
+                }
+                None => return ExitReason::Exited,
+            },
+            msg = socket.recv() => {
+                let input = match msg {
+                    Some(Ok(Message::Binary(data))) => data,
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<TerminalInput>(&text) {
+                        Ok(TerminalInput::Input { data }) => data.into_bytes(),
+                        Ok(TerminalInput::Resize { cols, rows }) => {
+                            if let Err(e) = manager.resize_terminal(&session.container, cols, rows).await {
+                                tracing::debug!("{}", e);
+                            }
+                            continue;
+                        }
+                        Err(e) => {
+                            if !send_frame(socket, &TerminalFrame::Error { message: format!("Invalid message: {}", e) }).await {
+                                return ExitReason::Closed;
+                            }
+                            continue;
+                        }
+                    },
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return ExitReason::Closed,
+                    _ => continue, // axum answers protocol pings itself
+                };
+                idle.as_mut().reset(tokio::time::Instant::now() + terminals.idle_timeout);
+                let written = session.input.write_all(&input).await;
+                if written.is_err() || session.input.flush().await.is_err() {
+                    return ExitReason::Exited;
+                }
+            },
+            _ = &mut idle => return ExitReason::IdleTimeout,
+            _ = &mut deadline => return ExitReason::MaxDuration,
+        }
+    }
+}
+
+/// Returns false once the socket is gone.
+async fn send_frame(socket: &mut WebSocket, frame: &TerminalFrame) -> bool {
+    let Ok(text) = serde_json::to_string(frame) else { return true };
+    socket.send(Message::Text(text)).await.is_ok()
+}
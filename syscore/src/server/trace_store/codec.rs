@@ -0,0 +1,248 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Media type of the compact encoding, for `Accept` negotiation.
+pub const COMPACT_CONTENT_TYPE: &str = "application/vnd.syscore.trace+msgpack";
+pub const COMPACT_VERSION: u32 = 1;
+
+/// MessagePack encoding of a list of trace events.
+///
+/// - `type`, `event`, `function` and `filename` are indices into `strings`.
+/// - `timestamp` and `process_time` are deltas from the previous event that had one.
+/// - `locals` only lists what changed since the previous event at the same
+///   `stack_depth`: `ls` holds added or changed entries (by interned name),
+///   `ld` removed names. `ls` is present whenever the event had locals at all.
+/// - Every other field is kept as is in `r`, and events that aren't objects
+///   at all in `x`.
+///
+/// Decoding replays the same state, so it gives back exactly the original events.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CompactTrace {
+    pub v: u32,
+    pub strings: Vec<String>,
+    pub events: Vec<PackedEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PackedEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    t: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    e: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    f: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ts: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pt: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ls: Option<Vec<(u32, Value)>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ld: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    r: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<Value>,
+}
+
+const INTERNED: [&str; 4] = ["type", "event", "function", "filename"];
+const DELTAS: [&str; 2] = ["timestamp", "process_time"];
+
+/// State both sides keep while walking the events.
+#[derive(Default)]
+struct Replay {
+    last_time: [u64; 2],
+    locals: HashMap<i64, Map<String, Value>>,
+}
+
+fn depth_of(fields: &Map<String, Value>) -> i64 {
+    fields.get("stack_depth").and_then(|d| d.as_i64()).unwrap_or(-1)
+}
+
+impl CompactTrace {
+    pub fn encode(events: &[Value]) -> Self {
+        let mut trace = CompactTrace { v: COMPACT_VERSION, ..Default::default() };
+        let mut index: HashMap<String, u32> = HashMap::new();
+        let mut replay = Replay::default();
+        let mut intern = |s: &str, strings: &mut Vec<String>| -> u32 {
+            *index.entry(s.to_string()).or_insert_with(|| {
+                strings.push(s.to_string());
+                (strings.len() - 1) as u32
+            })
+        };
+
+        for event in events {
+            let Some(fields) = event.as_object() else {
+                trace.events.push(PackedEvent { x: Some(event.clone()), ..Default::default() });
+                continue;
+            };
+            let mut packed = PackedEvent::default();
+            let mut rest = fields.clone();
+
+            for (slot, key) in INTERNED.iter().enumerate() {
+                if let Some(Value::String(s)) = rest.get(*key) {
+                    let id = Some(intern(s, &mut trace.strings));
+                    match slot {
+                        0 => packed.t = id,
+                        1 => packed.e = id,
+                        2 => packed.f = id,
+                        _ => packed.p = id,
+                    }
+                    rest.remove(*key);
+                }
+            }
+
+            for (slot, key) in DELTAS.iter().enumerate() {
+                if let Some(time) = rest.get(*key).and_then(|t| t.as_u64()) {
+                    let delta = Some(time as i64 - replay.last_time[slot] as i64);
+                    replay.last_time[slot] = time;
+                    if slot == 0 { packed.ts = delta } else { packed.pt = delta }
+                    rest.remove(*key);
+                }
+            }
+
+            if let Some(Value::Object(locals)) = rest.get("locals") {
+                let previous = replay.locals.entry(depth_of(&rest)).or_default();
+                let mut set = Vec::new();
+                for (name, value) in locals {
+                    if previous.get(name) != Some(value) {
+                        set.push((intern(name, &mut trace.strings), value.clone()));
+                    }
+                }
+                packed.ld = previous.keys()
+                    .filter(|name| !locals.contains_key(*name))
+                    .map(|name| intern(name, &mut trace.strings))
+                    .collect();
+                packed.ls = Some(set);
+                *previous = locals.clone();
+                rest.remove("locals");
+            }
+
+            packed.r = rest;
+            trace.events.push(packed);
+        }
+        trace
+    }
+
+    pub fn decode(self) -> Result<Vec<Value>, String> {
+        if self.v != COMPACT_VERSION {
+            return Err(format!("Unsupported compact trace version {}", self.v));
+        }
+        let string = |id: u32| self.strings.get(id as usize).cloned()
+            .ok_or_else(|| format!("Bad string index {}", id));
+        let mut replay = Replay::default();
+        let mut events = Vec::with_capacity(self.events.len());
+
+        for packed in &self.events {
+            if let Some(raw) = &packed.x {
+                events.push(raw.clone());
+                continue;
+            }
+            let mut fields = packed.r.clone();
+
+            for (key, id) in INTERNED.iter().zip([packed.t, packed.e, packed.f, packed.p]) {
+                if let Some(id) = id {
+                    fields.insert(key.to_string(), Value::String(string(id)?));
+                }
+            }
+            for (slot, (key, delta)) in DELTAS.iter().zip([packed.ts, packed.pt]).enumerate() {
+                if let Some(delta) = delta {
+                    let time = (replay.last_time[slot] as i64 + delta) as u64;
+                    replay.last_time[slot] = time;
+                    fields.insert(key.to_string(), Value::from(time));
+                }
+            }
+            if let Some(set) = &packed.ls {
+                let locals = replay.locals.entry(depth_of(&fields)).or_default();
+                for id in &packed.ld {
+                    locals.remove(&string(*id)?);
+                }
+                for (id, value) in set {
+                    locals.insert(string(*id)?, value.clone());
+                }
+                fields.insert("locals".to_string(), Value::Object(locals.clone()));
+            }
+
+            events.push(Value::Object(fields));
+        }
+        Ok(events)
+    }
+
+    /// MessagePack with field names, so any generic decoder can read it.
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, String> {
+        rmp_serde::to_vec_named(self).map_err(|e| e.to_string())
+    }
+}
+
+/// Compact encoding plus gzip, as stored on disk.
+pub fn encode_compressed(events: &[Value]) -> Result<Vec<u8>, String> {
+    let packed = CompactTrace::encode(events).to_msgpack()?;
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(&packed).map_err(|e| e.to_string())?;
+    gz.finish().map_err(|e| e.to_string())
+}
+
+pub fn decode_compressed(data: &[u8]) -> Result<Vec<Value>, String> {
+    let mut packed = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut packed).map_err(|e| e.to_string())?;
+    let trace: CompactTrace = rmp_serde::from_slice(&packed).map_err(|e| e.to_string())?;
+    trace.decode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(timestamp: u64, function: &str, depth: i64, locals: Value) -> Value {
+        json!({
+            "type": "Trace", "event": "line", "function": function, "filename": "main.py",
+            "line": timestamp, "timestamp": 1_000 + timestamp, "process_time": 10 * timestamp,
+            "stack_depth": depth, "locals": locals
+        })
+    }
+
+    /// Repeated functions, locals that change, vanish and come back per stack
+    /// depth, unicode, and events the encoding has no special case for.
+    fn events() -> Vec<Value> {
+        vec![
+            step(1, "main", 0, json!({ "n": 3 })),
+            step(2, "fib", 1, json!({ "k": 3, "name": "π ≈ 3.14" })),
+            step(3, "fib", 1, json!({ "k": 2, "name": "π ≈ 3.14" })),
+            step(4, "fib", 1, json!({ "name": "日本語 🚀" })),
+            step(5, "main", 0, json!({ "n": 3, "result": [1, 1, 2] })),
+            step(6, "main", 0, json!({})),
+            step(7, "fib", 1, json!({ "k": null })),
+            json!({ "type": "Stdout", "content": "héllo\n", "timestamp": 1_010 }),
+            json!({ "type": "Trace", "event": "return", "function": "main", "timestamp": 1_008.5, "locals": "n/a" }),
+            json!("not an object"),
+        ]
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let events = events();
+        assert_eq!(CompactTrace::encode(&events).decode().unwrap(), events);
+    }
+
+    #[test]
+    fn compressed_round_trips() {
+        let events = events();
+        assert_eq!(decode_compressed(&encode_compressed(&events).unwrap()).unwrap(), events);
+    }
+
+    #[test]
+    fn unchanged_locals_are_not_repeated() {
+        let trace = CompactTrace::encode(&events());
+        assert_eq!(trace.events[2].ls.as_ref().unwrap().len(), 1);
+        assert_eq!(trace.events[3].ld.len(), 1);
+        assert_eq!(trace.strings.iter().filter(|s| *s == "fib").count(), 1);
+    }
+}
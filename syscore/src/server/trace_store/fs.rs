@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::fs as tokio_fs;
+use super::codec;
+use super::manifest::{TraceInfo, TraceManifest};
+use super::{validate_job_id, TraceStore};
+
+/// Stores each trace as a directory `<dir>/<job_id>/` holding `manifest.json`
+/// and one file per chunk: `<index>.msgpack.gz` in the compact encoding, or
+/// `<index>.json` when `compact` is off. Either kind is read back.
+pub struct FsTraceStore {
+    dir: PathBuf,
+    compact: bool,
+}
+
+impl FsTraceStore {
+    pub fn new(dir: impl Into<PathBuf>, compact: bool) -> Result<Self, String> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(Self { dir, compact })
+    }
+
+    fn trace_dir(&self, job_id: &str) -> Result<PathBuf, String> {
+        validate_job_id(job_id)?;
+        Ok(self.dir.join(job_id))
+    }
+}
+
+#[async_trait]
+impl TraceStore for FsTraceStore {
+    fn kind(&self) -> &'static str {
+        "fs"
+    }
+
+    async fn put_chunk(&self, job_id: &str, index: usize, events: &[Value]) -> Result<(), String> {
+        let dir = self.trace_dir(job_id)?;
+        tokio_fs::create_dir_all(&dir).await.map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        if self.compact {
+            let data = codec::encode_compressed(events)?;
+            write_file(&dir.join(format!("{:06}.msgpack.gz", index)), data).await
+        } else {
+            write_json(&dir.join(format!("{:06}.json", index)), &events).await
+        }
+    }
+
+    async fn put_manifest(&self, manifest: &TraceManifest) -> Result<(), String> {
+        let dir = self.trace_dir(&manifest.job_id)?;
+        write_json(&dir.join("manifest.json"), manifest).await
+    }
+
+    async fn get_manifest(&self, job_id: &str) -> Result<Option<TraceManifest>, String> {
+        read_json(&self.trace_dir(job_id)?.join("manifest.json")).await
+    }
+
+    async fn get_chunk(&self, job_id: &str, index: usize) -> Result<Option<Vec<Value>>, String> {
+        let dir = self.trace_dir(job_id)?;
+        let compact = dir.join(format!("{:06}.msgpack.gz", index));
+        match tokio_fs::read(&compact).await {
+            Ok(data) => codec::decode_compressed(&data)
+                .map(Some)
+                .map_err(|e| format!("Failed to decode {}: {}", compact.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                read_json(&dir.join(format!("{:06}.json", index))).await
+            }
+            Err(e) => Err(format!("Failed to read {}: {}", compact.display(), e)),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<TraceInfo>, String> {
+        let mut traces = Vec::new();
+        let mut entries = tokio_fs::read_dir(&self.dir).await
+            .map_err(|e| format!("Failed to read {}: {}", self.dir.display(), e))?;
+        while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+            let path = entry.path().join("manifest.json");
+            if let Some(manifest) = read_json::<TraceManifest>(&path).await? {
+                traces.push(manifest.info());
+            }
+        }
+        Ok(traces)
+    }
+
+    async fn delete(&self, job_id: &str) -> Result<(), String> {
+        let dir = self.trace_dir(job_id)?;
+        match tokio_fs::remove_dir_all(&dir).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to delete {}: {}", dir.display(), e)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn delete_chunks(&self, job_id: &str) -> Result<(), String> {
+        let dir = self.trace_dir(job_id)?;
+        let mut entries = match tokio_fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+        };
+        while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+            if entry.file_name() != "manifest.json" {
+                let path = entry.path();
+                tokio_fs::remove_file(&path).await.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let data = serde_json::to_vec(value).map_err(|e| e.to_string())?;
+    write_file(path, data).await
+}
+
+// Write then rename so readers never see a half written file
+async fn write_file(path: &Path, data: Vec<u8>) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    tokio_fs::write(&tmp, data).await.map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    tokio_fs::rename(&tmp, path).await.map_err(|e| format!("Failed to store {}: {}", path.display(), e))
+}
+
+async fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    match tokio_fs::read(path).await {
+        Ok(data) => serde_json::from_slice(&data).map(Some).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::analysis::summary::TraceSummary;
+
+/// Where one chunk sits in the trace, kept in the manifest so step and time
+/// lookups only fetch the chunks they need.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkInfo {
+    pub index: usize,
+    pub first_step: usize,
+    pub len: usize,
+    /// Earliest and latest event `timestamp` (ns) in the chunk, if any event has one
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    /// Serialized size of the chunk
+    #[serde(default)]
+    pub bytes: u64,
+}
+
+/// Describes a stored trace. Written after all of its chunks, so a trace only
+/// becomes visible once it is complete.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceManifest {
+    pub job_id: String,
+    pub chunk_size: usize,
+    pub event_count: usize,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub chunks: Vec<ChunkInfo>,
+    pub summary: TraceSummary,
+    #[serde(default)]
+    pub stored_at: Option<DateTime<Utc>>,
+    /// Total serialized size of the chunks
+    #[serde(default)]
+    pub size_bytes: u64,
+    /// Set by retention once the events were dropped and only this manifest is left
+    #[serde(default)]
+    pub summary_only: bool,
+}
+
+/// What retention needs to know about a stored trace.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceInfo {
+    pub job_id: String,
+    pub stored_at: Option<DateTime<Utc>>,
+    pub size_bytes: u64,
+    pub summary_only: bool,
+}
+
+impl TraceManifest {
+    /// Splits `events` into chunks of `chunk_size` and describes them.
+    pub fn build<'a>(job_id: &str, events: &'a [Value], chunk_size: usize) -> (Self, Vec<&'a [Value]>) {
+        let chunk_size = chunk_size.max(1);
+        let parts: Vec<&[Value]> = events.chunks(chunk_size).collect();
+
+        let chunks: Vec<ChunkInfo> = parts.iter().enumerate().map(|(index, part)| {
+            let times = part.iter().filter_map(timestamp);
+            ChunkInfo {
+                index,
+                first_step: index * chunk_size,
+                len: part.len(),
+                start_time: times.clone().min(),
+                end_time: times.max(),
+                bytes: serde_json::to_vec(part).map(|v| v.len() as u64).unwrap_or(0),
+            }
+        }).collect();
+
+        let manifest = Self {
+            job_id: job_id.to_string(),
+            chunk_size,
+            event_count: events.len(),
+            start_time: chunks.iter().filter_map(|c| c.start_time).min(),
+            end_time: chunks.iter().filter_map(|c| c.end_time).max(),
+            size_bytes: chunks.iter().map(|c| c.bytes).sum(),
+            chunks,
+            summary: TraceSummary::from_events(events),
+            stored_at: Some(Utc::now()),
+            summary_only: false,
+        };
+        (manifest, parts)
+    }
+
+    pub fn info(&self) -> TraceInfo {
+        TraceInfo {
+            job_id: self.job_id.clone(),
+            stored_at: self.stored_at,
+            size_bytes: self.size_bytes,
+            summary_only: self.summary_only,
+        }
+    }
+
+    /// Chunks overlapping the steps `[from, to)`.
+    pub fn chunks_for_steps(&self, from: usize, to: usize) -> impl Iterator<Item = &ChunkInfo> {
+        self.chunks.iter().filter(move |c| c.first_step < to && c.first_step + c.len > from)
+    }
+
+    /// Chunks that may hold events between `start` and `end` (ns, inclusive).
+    pub fn chunks_for_time(&self, start: u64, end: u64) -> impl Iterator<Item = &ChunkInfo> {
+        self.chunks.iter().filter(move |c| match (c.start_time, c.end_time) {
+            (Some(s), Some(e)) => s <= end && e >= start,
+            _ => false,
+        })
+    }
+}
+
+pub fn timestamp(event: &Value) -> Option<u64> {
+    event.get("timestamp").and_then(|t| t.as_u64())
+}
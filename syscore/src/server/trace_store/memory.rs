@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use super::manifest::{TraceInfo, TraceManifest};
+use super::TraceStore;
+
+/// Keeps traces in process memory. Meant for tests and throwaway local runs;
+/// everything is lost on restart.
+#[derive(Default)]
+pub struct MemoryTraceStore {
+    manifests: RwLock<HashMap<String, TraceManifest>>,
+    chunks: RwLock<HashMap<(String, usize), Vec<Value>>>,
+}
+
+impl MemoryTraceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TraceStore for MemoryTraceStore {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn put_chunk(&self, job_id: &str, index: usize, events: &[Value]) -> Result<(), String> {
+        self.chunks.write().await.insert((job_id.to_string(), index), events.to_vec());
+        Ok(())
+    }
+
+    async fn put_manifest(&self, manifest: &TraceManifest) -> Result<(), String> {
+        self.manifests.write().await.insert(manifest.job_id.clone(), manifest.clone());
+        Ok(())
+    }
+
+    async fn get_manifest(&self, job_id: &str) -> Result<Option<TraceManifest>, String> {
+        Ok(self.manifests.read().await.get(job_id).cloned())
+    }
+
+    async fn get_chunk(&self, job_id: &str, index: usize) -> Result<Option<Vec<Value>>, String> {
+        Ok(self.chunks.read().await.get(&(job_id.to_string(), index)).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<TraceInfo>, String> {
+        Ok(self.manifests.read().await.values().map(TraceManifest::info).collect())
+    }
+
+    async fn delete(&self, job_id: &str) -> Result<(), String> {
+        self.manifests.write().await.remove(job_id);
+        self.delete_chunks(job_id).await
+    }
+
+    async fn delete_chunks(&self, job_id: &str) -> Result<(), String> {
+        self.chunks.write().await.retain(|(id, _), _| id != job_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+pub mod codec;
+pub mod fs;
+pub mod manifest;
+pub mod memory;
+pub mod retention;
+pub mod spool;
+pub mod supabase;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use std::env;
+use std::sync::Arc;
+use manifest::{TraceInfo, TraceManifest};
+
+pub const TRACE_DIR: &str = "storage/traces";
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+/// Where finished execution traces are kept. Traces are stored as ordered
+/// chunks of events plus a manifest; backends only implement the chunk and
+/// manifest primitives.
+#[async_trait]
+pub trait TraceStore: Send + Sync {
+    /// Short backend name, also used as the scheme of trace locations
+    fn kind(&self) -> &'static str;
+
+    /// Whether other syscore instances can read what this one stores. Cluster
+    /// workers store the traces of the jobs they run, and the coordinator
+    /// serves them, so both need a shared store.
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    /// Must overwrite an existing chunk, uploads are retried.
+    async fn put_chunk(&self, job_id: &str, index: usize, events: &[Value]) -> Result<(), String>;
+
+    async fn put_manifest(&self, manifest: &TraceManifest) -> Result<(), String>;
+
+    /// `Ok(None)` when the store has no (complete) trace for this job.
+    async fn get_manifest(&self, job_id: &str) -> Result<Option<TraceManifest>, String>;
+
+    async fn get_chunk(&self, job_id: &str, index: usize) -> Result<Option<Vec<Value>>, String>;
+
+    /// Every stored trace, for retention.
+    async fn list(&self) -> Result<Vec<TraceInfo>, String>;
+
+    /// Removes the trace entirely. Not an error if there is nothing stored.
+    async fn delete(&self, job_id: &str) -> Result<(), String>;
+
+    /// Removes the events but leaves the manifest alone.
+    async fn delete_chunks(&self, job_id: &str) -> Result<(), String>;
+
+    /// Drops the events and keeps only the manifest with its summary. The
+    /// manifest is updated first so readers never follow it to missing chunks.
+    /// Returns the number of bytes freed.
+    async fn summarize(&self, job_id: &str) -> Result<u64, String> {
+        let mut manifest = match self.get_manifest(job_id).await? {
+            Some(m) if !m.summary_only => m,
+            _ => return Ok(0),
+        };
+        let freed = manifest.size_bytes;
+        manifest.summary_only = true;
+        manifest.chunks.clear();
+        manifest.size_bytes = 0;
+        self.put_manifest(&manifest).await?;
+        self.delete_chunks(job_id).await?;
+        Ok(freed)
+    }
+
+    /// Chunks first, manifest last, so readers never see a partial trace.
+    async fn put(&self, job_id: &str, events: &[Value]) -> Result<(), String> {
+        validate_job_id(job_id)?;
+        let (manifest, parts) = TraceManifest::build(job_id, events, chunk_size());
+        for (index, part) in parts.into_iter().enumerate() {
+            self.put_chunk(job_id, index, part).await?;
+        }
+        self.put_manifest(&manifest).await
+    }
+
+    /// The whole trace. Prefer `read_steps` for anything large.
+    /// `Ok(None)` as well when only the summary is left.
+    async fn get(&self, job_id: &str) -> Result<Option<Vec<Value>>, String> {
+        match self.get_manifest(job_id).await? {
+            Some(manifest) if !manifest.summary_only => self.read_steps(&manifest, 0, manifest.event_count).await.map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Events for the steps `[from, to)`, fetching only the chunks involved.
+    async fn read_steps(&self, manifest: &TraceManifest, from: usize, to: usize) -> Result<Vec<Value>, String> {
+        let mut events = Vec::new();
+        for chunk in manifest.chunks_for_steps(from, to) {
+            let data = self.get_chunk(&manifest.job_id, chunk.index).await?
+                .ok_or_else(|| format!("Chunk {} of trace {} is missing", chunk.index, manifest.job_id))?;
+            let skip = from.saturating_sub(chunk.first_step);
+            let take = to.min(chunk.first_step + chunk.len) - (chunk.first_step + skip);
+            events.extend(data.into_iter().skip(skip).take(take));
+        }
+        Ok(events)
+    }
+
+    /// Step range `[from, to)` covering the events timestamped between `start`
+    /// and `end` (ns, inclusive), or `None` if nothing falls in that window.
+    async fn steps_for_time(&self, manifest: &TraceManifest, start: u64, end: u64) -> Result<Option<(usize, usize)>, String> {
+        let mut range: Option<(usize, usize)> = None;
+        for chunk in manifest.chunks_for_time(start, end) {
+            let data = self.get_chunk(&manifest.job_id, chunk.index).await?
+                .ok_or_else(|| format!("Chunk {} of trace {} is missing", chunk.index, manifest.job_id))?;
+            for (offset, event) in data.iter().enumerate() {
+                if manifest::timestamp(event).is_some_and(|t| t >= start && t <= end) {
+                    let step = chunk.first_step + offset;
+                    range = Some(match range {
+                        Some((from, _)) => (from, step + 1),
+                        None => (step, step + 1),
+                    });
+                }
+            }
+        }
+        Ok(range)
+    }
+
+    fn location(&self, job_id: &str) -> String {
+        format!("{}:{}", self.kind(), job_id)
+    }
+}
+
+pub type SharedTraceStore = Arc<dyn TraceStore>;
+
+/// Picks the backend from `SYSCORE_TRACE_STORE` (`fs` | `memory` | `supabase`).
+/// Without it, Supabase is used when configured with a service role key and the
+/// local filesystem otherwise.
+pub fn from_env() -> Result<SharedTraceStore, String> {
+    let choice = env::var("SYSCORE_TRACE_STORE").unwrap_or_default().to_lowercase();
+    let store: SharedTraceStore = match choice.as_str() {
+        "fs" => Arc::new(fs::FsTraceStore::new(trace_dir(), compact_storage())?),
+        "memory" => Arc::new(memory::MemoryTraceStore::new()),
+        "supabase" => Arc::new(supabase::SupabaseTraceStore::from_env()?),
+        "" => match supabase::SupabaseTraceStore::from_env() {
+            Ok(store) => Arc::new(store),
+            Err(e) => {
+                if supabase::SupabaseTraceStore::is_configured() {
+                    tracing::warn!("{}. Falling back to the local filesystem; traces will NOT reach Supabase.", e);
+                }
+                Arc::new(fs::FsTraceStore::new(trace_dir(), compact_storage())?)
+            }
+        },
+        other => return Err(format!("Unknown SYSCORE_TRACE_STORE: {}", other)),
+    };
+    Ok(store)
+}
+
+fn trace_dir() -> String {
+    env::var("SYSCORE_TRACE_DIR").unwrap_or_else(|_| TRACE_DIR.to_string())
+}
+
+/// Chunks are written in the compact encoding (`codec`) unless
+/// `SYSCORE_TRACE_ENCODING=json`. Only the fs backend stores binary chunks.
+fn compact_storage() -> bool {
+    !env::var("SYSCORE_TRACE_ENCODING").is_ok_and(|v| v.eq_ignore_ascii_case("json"))
+}
+
+/// Events per stored chunk, `SYSCORE_TRACE_CHUNK_SIZE` (default 1000).
+fn chunk_size() -> usize {
+    env::var("SYSCORE_TRACE_CHUNK_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_CHUNK_SIZE)
+}
+
+/// Job ids end up in file names and URLs, so only allow what we generate ourselves.
+pub fn validate_job_id(job_id: &str) -> Result<(), String> {
+    let valid = !job_id.is_empty()
+        && job_id.len() <= 64
+        && job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err("Invalid job id".to_string())
+    }
+}
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use crate::jobs::registry::JobRegistry;
+use super::SharedTraceStore;
+
+const DEFAULT_PURGE_INTERVAL_SECS: u64 = 3600;
+
+/// Limits on what the trace store keeps. Every limit is optional; with none
+/// set retention does nothing.
+#[derive(Clone, Debug, Serialize)]
+pub struct RetentionPolicy {
+    /// Traces older than this are deleted
+    pub max_age_days: Option<i64>,
+    /// Traces older than this keep only their manifest and summary
+    pub summary_after_days: Option<i64>,
+    /// Full traces beyond this total are deleted, oldest first
+    pub max_total_bytes: Option<u64>,
+    pub interval_secs: u64,
+}
+
+impl RetentionPolicy {
+    /// - `SYSCORE_TRACE_MAX_AGE_DAYS`
+    /// - `SYSCORE_TRACE_SUMMARY_AFTER_DAYS`
+    /// - `SYSCORE_TRACE_MAX_TOTAL_MB`
+    /// - `SYSCORE_TRACE_PURGE_INTERVAL`: seconds between runs (default 3600)
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            max_age_days: parse_var("SYSCORE_TRACE_MAX_AGE_DAYS")?,
+            summary_after_days: parse_var("SYSCORE_TRACE_SUMMARY_AFTER_DAYS")?,
+            max_total_bytes: parse_var::<u64>("SYSCORE_TRACE_MAX_TOTAL_MB")?.map(|mb| mb * 1024 * 1024),
+            interval_secs: parse_var("SYSCORE_TRACE_PURGE_INTERVAL")?.unwrap_or(DEFAULT_PURGE_INTERVAL_SECS),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.summary_after_days.is_some() || self.max_total_bytes.is_some()
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    match env::var(name) {
+        Ok(v) if !v.is_empty() => v.parse().map(Some).map_err(|_| format!("Invalid {}: {}", name, v)),
+        _ => Ok(None),
+    }
+}
+
+/// What one retention run removed.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PurgeReport {
+    pub ran_at: Option<DateTime<Utc>>,
+    pub deleted: Vec<String>,
+    pub summarized: Vec<String>,
+    pub bytes_freed: u64,
+    pub errors: Vec<String>,
+}
+
+/// Enforces a `RetentionPolicy` on the trace store and keeps the report of
+/// the last run around for the admin API.
+#[derive(Clone)]
+pub struct Retention {
+    store: SharedTraceStore,
+    jobs: JobRegistry,
+    policy: RetentionPolicy,
+    last_report: Arc<Mutex<Option<PurgeReport>>>,
+}
+
+impl Retention {
+    pub fn new(store: SharedTraceStore, jobs: JobRegistry, policy: RetentionPolicy) -> Self {
+        Self {
+            store,
+            jobs,
+            policy,
+            last_report: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    pub async fn last_report(&self) -> Option<PurgeReport> {
+        self.last_report.lock().await.clone()
+    }
+
+    /// Background task running the policy every `interval_secs`. Does nothing
+    /// when no limit is configured.
+    pub fn spawn(&self) {
+        if !self.policy.is_enabled() {
+            return;
+        }
+        let retention = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(retention.policy.interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                retention.run_once().await;
+            }
+        });
+    }
+
+    pub async fn run_once(&self) -> PurgeReport {
+        let mut report = PurgeReport { ran_at: Some(Utc::now()), ..Default::default() };
+
+        let mut traces = match self.store.list().await {
+            Ok(traces) => traces,
+            Err(e) => {
+                report.errors.push(format!("Failed to list traces: {}", e));
+                return self.finish(report).await;
+            }
+        };
+        // Oldest first; traces without a timestamp are never considered old
+        traces.sort_by_key(|t| t.stored_at.unwrap_or(DateTime::<Utc>::MAX_UTC));
+
+        let now = Utc::now();
+        let older_than = |days: Option<i64>, at: Option<DateTime<Utc>>| match (days, at) {
+            (Some(days), Some(at)) => now - at > ChronoDuration::days(days),
+            _ => false,
+        };
+
+        let mut kept = Vec::new();
+        for trace in traces {
+            if older_than(self.policy.max_age_days, trace.stored_at) {
+                self.delete(&trace.job_id, trace.size_bytes, &mut report).await;
+            } else if !trace.summary_only && older_than(self.policy.summary_after_days, trace.stored_at) {
+                match self.store.summarize(&trace.job_id).await {
+                    Ok(freed) => {
+                        report.bytes_freed += freed;
+                        report.summarized.push(trace.job_id);
+                    }
+                    Err(e) => report.errors.push(format!("{}: {}", trace.job_id, e)),
+                }
+            } else {
+                kept.push(trace);
+            }
+        }
+
+        if let Some(max) = self.policy.max_total_bytes {
+            let mut total: u64 = kept.iter().map(|t| t.size_bytes).sum();
+            for trace in kept.iter().filter(|t| !t.summary_only) {
+                if total <= max {
+                    break;
+                }
+                total -= trace.size_bytes;
+                self.delete(&trace.job_id, trace.size_bytes, &mut report).await;
+            }
+        }
+
+        self.finish(report).await
+    }
+
+    async fn delete(&self, job_id: &str, size: u64, report: &mut PurgeReport) {
+        match self.store.delete(job_id).await {
+            Ok(()) => {
+                self.jobs.update(job_id, |r| r.trace_location = None).await;
+                report.bytes_freed += size;
+                report.deleted.push(job_id.to_string());
+            }
+            Err(e) => report.errors.push(format!("{}: {}", job_id, e)),
+        }
+    }
+
+    async fn finish(&self, report: PurgeReport) -> PurgeReport {
+        if !report.deleted.is_empty() || !report.summarized.is_empty() || !report.errors.is_empty() {
+            tracing::info!(
+                "Trace retention: deleted {}, summarized {}, freed {} bytes, {} errors",
+                report.deleted.len(), report.summarized.len(), report.bytes_freed, report.errors.len()
+            );
+            for e in &report.errors {
+                tracing::warn!("Trace retention: {}", e);
+            }
+        }
+        *self.last_report.lock().await = Some(report.clone());
+        report
+    }
+}
@@ -0,0 +1,246 @@
+use crate::jobs::registry::{JobRegistry, JobState};
+use rand::Rng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs as tokio_fs;
+use tokio::sync::{Mutex, Notify};
+use super::{validate_job_id, SharedTraceStore};
+
+pub const SPOOL_DIR: &str = "storage/spool";
+
+const BASE_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const IDLE_WAKEUP: Duration = Duration::from_secs(30);
+
+pub enum UploadOutcome {
+    /// Stored right away, with the trace location
+    Stored(String),
+    /// The store failed, the trace is on disk and will be retried in the background
+    Spooled,
+    /// Taken down by `discard` while it was uploading, so it wasn't kept
+    Discarded,
+}
+
+struct PendingUpload {
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+/// Uploads traces to the active store and, when that fails, keeps them in a
+/// local on-disk spool (`<dir>/<job_id>.json`) that a background task retries
+/// with exponential backoff and jitter. Uploads are keyed by job id so a retry
+/// after a partial success overwrites rather than duplicates.
+#[derive(Clone)]
+pub struct TraceSpool {
+    dir: PathBuf,
+    store: SharedTraceStore,
+    jobs: JobRegistry,
+    pending: Arc<Mutex<HashMap<String, PendingUpload>>>,
+    // Held by an upload, or a retry from reading the spooled trace until the
+    // job record is updated, so `discard` waits for an upload already under way
+    uploading: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+    // Jobs a `discard` is waiting on, so an upload finishing meanwhile backs out
+    discarding: Arc<Mutex<HashSet<String>>>,
+    wake: Arc<Notify>,
+}
+
+impl TraceSpool {
+    pub fn new(dir: impl Into<PathBuf>, store: SharedTraceStore, jobs: JobRegistry) -> Result<Self, String> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        // Anything left from a previous run is retried right away
+        let mut pending = HashMap::new();
+        for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(job_id) = path.file_stem().and_then(|s| s.to_str()) {
+                pending.insert(job_id.to_string(), PendingUpload { attempts: 0, next_attempt: Instant::now() });
+            }
+        }
+        if !pending.is_empty() {
+            tracing::info!("Trace spool has {} pending uploads from a previous run", pending.len());
+        }
+
+        Ok(Self {
+            dir,
+            store,
+            jobs,
+            pending: Arc::new(Mutex::new(pending)),
+            uploading: Arc::new(Mutex::new(HashMap::new())),
+            discarding: Arc::new(Mutex::new(HashSet::new())),
+            wake: Arc::new(Notify::new()),
+        })
+    }
+
+    fn path(&self, job_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", job_id))
+    }
+
+    pub async fn upload(&self, job_id: &str, events: &[Value]) -> Result<UploadOutcome, String> {
+        let lock = self.upload_lock(job_id).await;
+        let _uploading = lock.lock().await;
+        let outcome = match self.store.put(job_id, events).await {
+            Ok(()) if self.discarding.lock().await.contains(job_id) => {
+                tracing::info!("[Job {}] Trace taken down while uploading, deleting it again", job_id);
+                let _ = self.store.delete(job_id).await;
+                Ok(UploadOutcome::Discarded)
+            }
+            Ok(()) => Ok(UploadOutcome::Stored(self.store.location(job_id))),
+            Err(e) => {
+                tracing::warn!("[Job {}] Trace upload failed ({}), spooling for retry", job_id, e);
+                self.enqueue(job_id, events).await.map(|()| UploadOutcome::Spooled)
+            }
+        };
+        if !self.is_pending(job_id).await {
+            self.uploading.lock().await.remove(job_id);
+        }
+        outcome
+    }
+
+    async fn enqueue(&self, job_id: &str, events: &[Value]) -> Result<(), String> {
+        validate_job_id(job_id)?;
+        let path = self.path(job_id);
+        let data = serde_json::to_vec(events).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        tokio_fs::write(&tmp, data).await.map_err(|e| format!("Failed to spool trace: {}", e))?;
+        tokio_fs::rename(&tmp, &path).await.map_err(|e| format!("Failed to spool trace: {}", e))?;
+
+        self.pending.lock().await.insert(job_id.to_string(), PendingUpload {
+            attempts: 1,
+            next_attempt: Instant::now() + backoff(1),
+        });
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Drops a spooled trace that hasn't been uploaded yet. Returns whether there was one.
+    ///
+    /// If the trace is being uploaded right now, waits for that to finish first,
+    /// so once this returns the trace is either gone from the spool or in the
+    /// store. A first upload finishing meanwhile deletes its own copy again.
+    pub async fn discard(&self, job_id: &str) -> Result<bool, String> {
+        validate_job_id(job_id)?;
+        self.discarding.lock().await.insert(job_id.to_string());
+        let lock = self.upload_lock(job_id).await;
+        let uploading = lock.lock().await;
+        let was_pending = self.pending.lock().await.remove(job_id).is_some();
+        self.uploading.lock().await.remove(job_id);
+        let removed = tokio_fs::remove_file(self.path(job_id)).await;
+        drop(uploading);
+        self.discarding.lock().await.remove(job_id);
+        match removed {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(was_pending),
+            Err(e) => Err(format!("Failed to remove spooled trace: {}", e)),
+        }
+    }
+
+    async fn upload_lock(&self, job_id: &str) -> Arc<Mutex<()>> {
+        self.uploading.lock().await.entry(job_id.to_string()).or_default().clone()
+    }
+
+    async fn is_pending(&self, job_id: &str) -> bool {
+        self.pending.lock().await.contains_key(job_id)
+    }
+
+    /// Background task retrying spooled uploads until they succeed.
+    pub fn spawn_retry_loop(&self) {
+        let spool = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let sleep_for = spool.retry_due().await;
+                let _ = tokio::time::timeout(sleep_for, spool.wake.notified()).await;
+            }
+        });
+    }
+
+    /// Retries every due upload and returns how long to sleep until the next one.
+    async fn retry_due(&self) -> Duration {
+        let now = Instant::now();
+        let due: Vec<String> = self.pending.lock().await.iter()
+            .filter(|(_, p)| p.next_attempt <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for job_id in due {
+            self.retry(&job_id).await;
+        }
+
+        let pending = self.pending.lock().await;
+        pending.values()
+            .map(|p| p.next_attempt.saturating_duration_since(Instant::now()))
+            .min()
+            .unwrap_or(IDLE_WAKEUP)
+    }
+
+    async fn retry(&self, job_id: &str) {
+        let lock = self.upload_lock(job_id).await;
+        let _uploading = lock.lock().await;
+        self.retry_locked(job_id).await;
+        if !self.is_pending(job_id).await {
+            self.uploading.lock().await.remove(job_id);
+        }
+    }
+
+    async fn retry_locked(&self, job_id: &str) {
+        // Discarded since it became due
+        if !self.is_pending(job_id).await {
+            return;
+        }
+        let path = self.path(job_id);
+        let events: Vec<Value> = match tokio_fs::read(&path).await.map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+        {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::error!("[Job {}] Dropping unreadable spooled trace: {}", job_id, e);
+                let _ = tokio_fs::remove_file(&path).await;
+                self.pending.lock().await.remove(job_id);
+                self.jobs.transition(job_id, JobState::Failed, Some(format!("Spooled trace unreadable: {}", e))).await;
+                return;
+            }
+        };
+
+        if !self.is_pending(job_id).await {
+            return;
+        }
+        match self.store.put(job_id, &events).await {
+            Ok(()) => {
+                tracing::info!("[Job {}] Spooled trace uploaded", job_id);
+                let _ = tokio_fs::remove_file(&path).await;
+                if self.pending.lock().await.remove(job_id).is_none() {
+                    // Discarded while uploading: don't report it as stored
+                    let _ = self.store.delete(job_id).await;
+                    return;
+                }
+                let location = self.store.location(job_id);
+                self.jobs.update(job_id, |r| r.trace_location = Some(location)).await;
+                self.jobs.transition(job_id, JobState::Completed, Some("trace uploaded after retry".to_string())).await;
+            }
+            Err(e) => {
+                let mut pending = self.pending.lock().await;
+                if let Some(p) = pending.get_mut(job_id) {
+                    p.attempts += 1;
+                    let delay = backoff(p.attempts);
+                    p.next_attempt = Instant::now() + delay;
+                    tracing::warn!("[Job {}] Spooled upload attempt {} failed ({}), next in {:?}", job_id, p.attempts, e, delay);
+                }
+            }
+        }
+    }
+}
+
+/// Exponential backoff capped at `MAX_BACKOFF`, randomised between half and the
+/// full delay so many spooled traces don't hammer the store in lockstep once it
+/// comes back.
+fn backoff(attempts: u32) -> Duration {
+    let exp = BASE_BACKOFF.saturating_mul(1u32 << attempts.min(16)).min(MAX_BACKOFF);
+    let millis = rand::thread_rng().gen_range(exp.as_millis() as u64 / 2..=exp.as_millis() as u64);
+    Duration::from_millis(millis)
+}
@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use super::manifest::{TraceInfo, TraceManifest};
+use super::{validate_job_id, TraceStore};
+
+#[derive(Serialize)]
+struct ChunkRow<'a> {
+    job_id: &'a str,
+    chunk_index: usize,
+    events: &'a [Value],
+}
+
+#[derive(Deserialize)]
+struct ChunkEvents {
+    events: Vec<Value>,
+}
+
+// PostgREST caps responses, so listings are fetched in pages
+const LIST_PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize)]
+struct LegacyRow {
+    trace_data: Vec<Value>,
+}
+
+/// Stores traces in the `trace_manifests` and `trace_chunks` tables through
+/// Supabase REST. Traces uploaded before chunking are still read from
+/// `execution_traces`.
+pub struct SupabaseTraceStore {
+    client: Client,
+    url: String,
+    key: String,
+}
+
+impl SupabaseTraceStore {
+    /// Needs `SUPABASE_SERVICE_ROLE_KEY`: the trace tables are read-only for the
+    /// anon key the web app uses, so every upload with it would be rejected.
+    pub fn from_env() -> Result<Self, String> {
+        let url = env::var("SUPABASE_URL")
+            .or_else(|_| env::var("VITE_SUPABASE_URL"))
+            .map_err(|_| "Supabase URL not configured".to_string())?;
+
+        let key = env::var("SUPABASE_SERVICE_ROLE_KEY").map_err(|_| {
+            if has_anon_key() {
+                "Supabase is configured with an anon key only; storing traces needs SUPABASE_SERVICE_ROLE_KEY".to_string()
+            } else {
+                "Supabase Key not configured".to_string()
+            }
+        })?;
+
+        Ok(Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            key,
+        })
+    }
+
+    /// Whether a Supabase project is configured at all, service role key or not.
+    pub fn is_configured() -> bool {
+        (env::var("SUPABASE_URL").is_ok() || env::var("VITE_SUPABASE_URL").is_ok())
+            && (env::var("SUPABASE_SERVICE_ROLE_KEY").is_ok() || has_anon_key())
+    }
+
+    fn table_url(&self, table: &str) -> String {
+        format!("{}/rest/v1/{}", self.url, table)
+    }
+
+    fn authed(&self, req: RequestBuilder) -> RequestBuilder {
+        req.header("apikey", &self.key)
+            .header("Authorization", format!("Bearer {}", self.key))
+    }
+
+    // Upsert so retried uploads never create duplicate rows
+    async fn upsert<T: Serialize + ?Sized>(&self, table: &str, on_conflict: &str, row: &T) -> Result<(), String> {
+        let res = self.authed(self.client.post(self.table_url(table)))
+            .query(&[("on_conflict", on_conflict)])
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(row)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Supabase upload failed: {:?}", res.text().await))
+        }
+    }
+
+    async fn select<T: for<'de> Deserialize<'de>>(&self, table: &str, query: &[(&str, String)]) -> Result<Option<T>, String> {
+        Ok(self.select_all(table, query).await?.into_iter().next())
+    }
+
+    async fn select_all<T: for<'de> Deserialize<'de>>(&self, table: &str, query: &[(&str, String)]) -> Result<Vec<T>, String> {
+        let res = self.authed(self.client.get(self.table_url(table)))
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !res.status().is_success() {
+            return Err(format!("Supabase fetch failed: {:?}", res.text().await));
+        }
+        res.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn delete_rows(&self, table: &str, job_id: &str) -> Result<(), String> {
+        validate_job_id(job_id)?;
+        let res = self.authed(self.client.delete(self.table_url(table)))
+            .query(&[("job_id", format!("eq.{}", job_id))])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Supabase delete failed: {:?}", res.text().await))
+        }
+    }
+}
+
+#[async_trait]
+impl TraceStore for SupabaseTraceStore {
+    fn kind(&self) -> &'static str {
+        "supabase"
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+
+    async fn put_chunk(&self, job_id: &str, index: usize, events: &[Value]) -> Result<(), String> {
+        let row = ChunkRow { job_id, chunk_index: index, events };
+        self.upsert("trace_chunks", "job_id,chunk_index", &row).await
+    }
+
+    async fn put_manifest(&self, manifest: &TraceManifest) -> Result<(), String> {
+        self.upsert("trace_manifests", "job_id", manifest).await
+    }
+
+    async fn get_manifest(&self, job_id: &str) -> Result<Option<TraceManifest>, String> {
+        validate_job_id(job_id)?;
+        self.select("trace_manifests", &[("job_id", format!("eq.{}", job_id))]).await
+    }
+
+    async fn get_chunk(&self, job_id: &str, index: usize) -> Result<Option<Vec<Value>>, String> {
+        validate_job_id(job_id)?;
+        let row: Option<ChunkEvents> = self.select("trace_chunks", &[
+            ("job_id", format!("eq.{}", job_id)),
+            ("chunk_index", format!("eq.{}", index)),
+            ("select", "events".to_string()),
+        ]).await?;
+        Ok(row.map(|r| r.events))
+    }
+
+    async fn list(&self) -> Result<Vec<TraceInfo>, String> {
+        let mut traces = Vec::new();
+        loop {
+            let page: Vec<TraceInfo> = self.select_all("trace_manifests", &[
+                ("select", "job_id,stored_at,size_bytes,summary_only".to_string()),
+                ("order", "job_id".to_string()),
+                ("limit", LIST_PAGE_SIZE.to_string()),
+                ("offset", traces.len().to_string()),
+            ]).await?;
+            let done = page.len() < LIST_PAGE_SIZE;
+            traces.extend(page);
+            if done {
+                return Ok(traces);
+            }
+        }
+    }
+
+    // Legacy rows go too, a takedown has to cover traces from before chunking
+    async fn delete(&self, job_id: &str) -> Result<(), String> {
+        self.delete_rows("trace_manifests", job_id).await?;
+        self.delete_rows("trace_chunks", job_id).await?;
+        self.delete_rows("execution_traces", job_id).await
+    }
+
+    async fn delete_chunks(&self, job_id: &str) -> Result<(), String> {
+        self.delete_rows("trace_chunks", job_id).await
+    }
+
+    async fn get(&self, job_id: &str) -> Result<Option<Vec<Value>>, String> {
+        if let Some(manifest) = self.get_manifest(job_id).await? {
+            if manifest.summary_only {
+                return Ok(None);
+            }
+            return self.read_steps(&manifest, 0, manifest.event_count).await.map(Some);
+        }
+        let row: Option<LegacyRow> = self.select("execution_traces", &[
+            ("job_id", format!("eq.{}", job_id)),
+            ("select", "trace_data".to_string()),
+        ]).await?;
+        Ok(row.map(|r| r.trace_data))
+    }
+}
+
+fn has_anon_key() -> bool {
+    env::var("SUPABASE_ANON_KEY").is_ok() || env::var("VITE_SUPABASE_ANON_KEY").is_ok()
+}
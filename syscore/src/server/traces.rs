@@ -0,0 +1,332 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::analysis::calls::Clock;
+use crate::analysis::diff::{self, DiffInput, TraceDiff};
+use crate::analysis::export::{self, ExportFormat};
+use crate::analysis::query::{QueryCursor, QueryState, TraceFilter};
+use crate::analysis::summary::TraceSummary;
+use super::state::AppState;
+use super::trace_store::codec::{CompactTrace, COMPACT_CONTENT_TYPE};
+use super::trace_store::manifest::TraceManifest;
+use super::trace_store::validate_job_id;
+
+const DEFAULT_PAGE_LIMIT: usize = 1000;
+const MAX_PAGE_LIMIT: usize = 10_000;
+const DEFAULT_QUERY_LIMIT: usize = 100;
+const MAX_QUERY_LIMIT: usize = 1000;
+/// Events scanned per query request, so sparse queries over huge traces
+/// still answer quickly. The cursor picks up where the scan stopped.
+const MAX_QUERY_SCAN: usize = 100_000;
+
+/// Either a step range (`from`/`to`, zero based, `to` exclusive) or a time
+/// range (`start_time`/`end_time`, event timestamps in ns). Results are capped
+/// at `limit` events; follow `next_step` for the rest.
+#[derive(Deserialize)]
+pub struct TraceEventsQuery {
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TraceExportQuery {
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub clock: Clock,
+}
+
+#[derive(Deserialize)]
+pub struct TraceDiffQuery {
+    pub a: String,
+    pub b: String,
+    #[serde(default)]
+    pub clock: Clock,
+}
+
+/// Paging for trace queries; the filters themselves are `TraceFilter`.
+#[derive(Deserialize)]
+pub struct TraceQueryPaging {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct StepEvent {
+    /// Index of the event in the full trace
+    pub step: usize,
+    pub event: Value,
+}
+
+#[derive(Serialize)]
+pub struct TraceQueryPage {
+    pub job_id: String,
+    pub event_count: usize,
+    pub matches: Vec<StepEvent>,
+    /// Pass back as `cursor` for more; absent once the whole trace was scanned
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TraceEventsPage {
+    pub job_id: String,
+    pub event_count: usize,
+    pub from_step: usize,
+    pub events: Vec<Value>,
+    /// First step after this page when the requested range goes on
+    pub next_step: Option<usize>,
+}
+
+/// `TraceEventsPage` in the compact encoding: `events` become `trace`.
+#[derive(Serialize)]
+pub struct CompactEventsPage {
+    pub job_id: String,
+    pub event_count: usize,
+    pub from_step: usize,
+    pub trace: CompactTrace,
+    pub next_step: Option<usize>,
+}
+
+/// Reads a finished trace back from whichever trace store is active. Sent as
+/// a `CompactTrace` when the client accepts `COMPACT_CONTENT_TYPE`.
+pub async fn get_trace_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let events = load_events(&state, &job_id).await?;
+    if wants_compact(&headers) {
+        msgpack(&CompactTrace::encode(&events))
+    } else {
+        Ok(([(header::VARY, "Accept")], Json(events)).into_response())
+    }
+}
+
+pub async fn get_trace_manifest_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<TraceManifest>, (StatusCode, String)> {
+    load_manifest(&state, &job_id).await.map(Json)
+}
+
+/// The analytics computed when the trace was stored. Still there after
+/// retention reduced the trace to its summary.
+pub async fn get_trace_summary_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<TraceSummary>, (StatusCode, String)> {
+    load_manifest(&state, &job_id).await.map(|m| Json(m.summary))
+}
+
+pub async fn get_trace_events_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    Query(params): Query<TraceEventsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let manifest = load_manifest(&state, &job_id).await?;
+    if manifest.summary_only {
+        return Err((StatusCode::GONE, format!("Trace for job {} was reduced to its summary by retention", job_id)));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+    let (from, to) = if params.start_time.is_some() || params.end_time.is_some() {
+        let start = params.start_time.unwrap_or(0);
+        let end = params.end_time.unwrap_or(u64::MAX);
+        if start > end {
+            return Err((StatusCode::BAD_REQUEST, "start_time is after end_time".to_string()));
+        }
+        let range = state.traces.steps_for_time(&manifest, start, end).await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        // `from` still applies inside the time window, to page through it
+        match range {
+            Some((first, end)) => (params.from.unwrap_or(0).max(first), end),
+            None => (0, 0),
+        }
+    } else {
+        (params.from.unwrap_or(0), params.to.unwrap_or(manifest.event_count))
+    };
+
+    let to = to.min(manifest.event_count);
+    let page_end = to.min(from.saturating_add(limit));
+    let events = if from < page_end {
+        state.traces.read_steps(&manifest, from, page_end).await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+    } else {
+        Vec::new()
+    };
+
+    let next_step = (page_end < to).then_some(page_end);
+    if wants_compact(&headers) {
+        return msgpack(&CompactEventsPage {
+            job_id,
+            event_count: manifest.event_count,
+            from_step: from,
+            trace: CompactTrace::encode(&events),
+            next_step,
+        });
+    }
+    let page = TraceEventsPage {
+        job_id,
+        event_count: manifest.event_count,
+        from_step: from,
+        events,
+        next_step,
+    };
+    Ok(([(header::VARY, "Accept")], Json(page)).into_response())
+}
+
+/// Filters a stored trace, scanning it chunk by chunk. A page may hold fewer
+/// than `limit` matches (even none) when the scan budget ran out first.
+pub async fn query_trace_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    Query(filter): Query<TraceFilter>,
+    Query(paging): Query<TraceQueryPaging>,
+) -> Result<Json<TraceQueryPage>, (StatusCode, String)> {
+    let manifest = load_manifest(&state, &job_id).await?;
+    if manifest.summary_only {
+        return Err((StatusCode::GONE, format!("Trace for job {} was reduced to its summary by retention", job_id)));
+    }
+    let limit = paging.limit.unwrap_or(DEFAULT_QUERY_LIMIT).clamp(1, MAX_QUERY_LIMIT);
+    let mut cursor = match paging.cursor.as_deref() {
+        Some(c) if !c.is_empty() => QueryCursor::decode(c).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+        _ => QueryCursor::default(),
+    };
+
+    let timed = filter.start_time.is_some() || filter.end_time.is_some();
+    let (start, end) = (filter.start_time.unwrap_or(0), filter.end_time.unwrap_or(u64::MAX));
+    let mut matches = Vec::new();
+    let mut scanned = 0;
+
+    // Filters that follow the stack replay the steps before the cursor to
+    // rebuild their state; those don't count against the scan budget
+    let resume_at = cursor.step;
+    let mut stack = QueryState::default();
+    let replay = filter.is_stateful();
+    for chunk in manifest.chunks.iter().filter(|c| replay || c.first_step + c.len > resume_at) {
+        if matches.len() >= limit || scanned >= MAX_QUERY_SCAN {
+            break;
+        }
+        // Chunks outside the time window can't match, unless the filter
+        // needs to follow the stack through them
+        let in_window = match (chunk.start_time, chunk.end_time) {
+            (Some(s), Some(e)) => s <= end && e >= start,
+            _ => false,
+        };
+        if timed && !in_window && !filter.is_stateful() {
+            cursor.step = chunk.first_step + chunk.len;
+            continue;
+        }
+
+        let events = state.traces.get_chunk(&job_id, chunk.index).await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+            .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, format!("Chunk {} of trace {} is missing", chunk.index, job_id)))?;
+        for (offset, event) in events.into_iter().enumerate() {
+            let step = chunk.first_step + offset;
+            if step < resume_at {
+                if replay {
+                    filter.matches(&mut stack, &event);
+                }
+                continue;
+            }
+            cursor.step = step + 1;
+            scanned += 1;
+            if filter.matches(&mut stack, &event) {
+                matches.push(StepEvent { step, event });
+                if matches.len() >= limit {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(Json(TraceQueryPage {
+        job_id,
+        event_count: manifest.event_count,
+        matches,
+        next_cursor: (cursor.step < manifest.event_count).then(|| cursor.encode()),
+    }))
+}
+
+/// Compares two stored traces (`a` is the baseline, deltas are `b - a`).
+pub async fn diff_traces_handler(
+    State(state): State<AppState>,
+    Query(params): Query<TraceDiffQuery>,
+) -> Result<Json<TraceDiff>, (StatusCode, String)> {
+    let a = load_events(&state, &params.a).await?;
+    let b = load_events(&state, &params.b).await?;
+    // Legacy traces have no manifest, their calls are counted from the events
+    let a_manifest = state.traces.get_manifest(&params.a).await.ok().flatten();
+    let b_manifest = state.traces.get_manifest(&params.b).await.ok().flatten();
+    Ok(Json(diff::diff(
+        DiffInput { job_id: &params.a, events: &a, summary: a_manifest.as_ref().map(|m| &m.summary) },
+        DiffInput { job_id: &params.b, events: &b, summary: b_manifest.as_ref().map(|m| &m.summary) },
+        params.clock,
+    )))
+}
+
+/// Converts a stored trace for external tools (`format=chrome|speedscope`,
+/// `clock=wall|process`). Served as a download.
+pub async fn export_trace_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    Query(params): Query<TraceExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let events = load_events(&state, &job_id).await?;
+    let body = export::export(params.format, &job_id, &events, params.clock);
+    let disposition = format!("attachment; filename=\"{}\"", params.format.file_name(&job_id));
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(body)))
+}
+
+/// True if an Accept media range names the compact type with a non-zero `q`
+/// (`;q=0` means the client refuses it).
+fn wants_compact(headers: &HeaderMap) -> bool {
+    headers.get_all(header::ACCEPT).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            if !parts.next().is_some_and(|t| t.eq_ignore_ascii_case(COMPACT_CONTENT_TYPE)) {
+                return false;
+            }
+            let q = parts
+                .filter_map(|p| p.split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("q"))
+                .map(|(_, v)| v.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            q > 0.0
+        })
+}
+
+fn msgpack<T: Serialize>(body: &T) -> Result<Response, (StatusCode, String)> {
+    let data = rmp_serde::to_vec_named(body).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(([(header::CONTENT_TYPE, COMPACT_CONTENT_TYPE), (header::VARY, "Accept")], data).into_response())
+}
+
+async fn load_events(state: &AppState, job_id: &str) -> Result<Vec<Value>, (StatusCode, String)> {
+    validate_job_id(job_id).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match state.traces.get(job_id).await {
+        Ok(Some(events)) => Ok(events),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("No trace for job {}", job_id))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn load_manifest(state: &AppState, job_id: &str) -> Result<TraceManifest, (StatusCode, String)> {
+    validate_job_id(job_id).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match state.traces.get_manifest(job_id).await {
+        Ok(Some(manifest)) => Ok(manifest),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("No trace for job {}", job_id))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
@@ -3,68 +3,285 @@ use axum::{
     response::IntoResponse,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use crate::docker::manager::ContainerManager;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use crate::jobs::events::{JobEvents, StreamEvent, Subscription};
+use crate::jobs::registry::JobState;
+use crate::docker::manager::{DebugRequest, StdinInput};
+use super::routes::{cancel_job, write_stdin};
+use super::state::AppState;
+
+/// Frames queued for a slow client before subscriptions start to lag
+const OUTGOING_CAPACITY: usize = 256;
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+/// What a client sends on `/ws/stream`. `id` is optional and echoed back on
+/// the reply, so requests can be matched with their answers.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Streams from `from_seq` on (default 0, everything still buffered).
+    /// After a reconnect, pass the last seq seen plus one.
+    Subscribe {
+        id: Option<Value>,
+        job_id: String,
+        #[serde(default)]
+        from_seq: u64,
+    },
+    Unsubscribe { id: Option<Value>, job_id: String },
+    // Everything that acts on a job needs the `control_key` its detached
+    // `/api/execute` returned
+    Cancel { id: Option<Value>, job_id: String, control_key: Option<String> },
+    /// Input for an interactive job, written to its stdin as is
+    Stdin { id: Option<Value>, job_id: String, control_key: Option<String>, data: String },
+    /// Closes an interactive job's stdin
+    StdinEof { id: Option<Value>, job_id: String, control_key: Option<String> },
+    /// A debugger request for a job started with `debug`; the answer comes
+    /// back as a `Debug` event on the job's stream
+    Debug { id: Option<Value>, job_id: String, control_key: Option<String>, request: DebugRequest },
+    Ping { id: Option<Value> },
+}
+
+/// What the server sends. Events carry their job id and the job's sequence
+/// number, so several subscriptions can share one socket.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum ServerFrame {
+    /// A request went through; `request` is its type
+    Ack {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<Value>,
+        request: &'static str,
+        job_id: String,
+    },
+    Pong {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<Value>,
+    },
+    Event { job_id: String, seq: u64, event: Box<RawValue> },
+    /// The job's stream is over, the subscription is gone
+    End { job_id: String },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        job_id: Option<String>,
+        message: String,
+    },
+}
+
+impl ServerFrame {
+    fn error(id: Option<Value>, job_id: Option<&str>, message: impl Into<String>) -> Self {
+        ServerFrame::Error { id, job_id: job_id.map(str::to_string), message: message.into() }
+    }
+}
 
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
-    State(manager): State<ContainerManager>,
+    State(state): State<AppState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, manager))
+    ws.on_upgrade(|socket| handle_socket(socket, state))
 }
 
-async fn handle_socket(socket: WebSocket, manager: ContainerManager) {
+async fn handle_socket(socket: WebSocket, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
 
-    // In a real implementation we would:
-    // 1. Receive a "subscribe" message with jobId
-    // 2. Look up the job/container
-    // 3. Spawn a tokio task to stream logs/profiling data to 'sender'
-    
-    while let Some(msg) = receiver.next().await {
-        if let Ok(msg) = msg {
-            if let Message::Text(text) = msg {
-                // Parse message (expecting simple "subscribe:job_id" for now)
-                if text.starts_with("subscribe:") {
-                    let job_id = text.trim_start_matches("subscribe:").trim();
-                    tracing::info!("WS subscribing to job: {}", job_id);
-                    
-                    if let Some(mut rx) = manager.subscribe(job_id).await {
-                        // Spawn a task to forward broadcast messages to this websocket
-                        // Note: Requires cloning sender, which is actually tricky with axum's split sink
-                        // Easier approach: Just loop here and select! between receiver and broadcast
-                        
-                        loop {
-                            tokio::select! {
-                                // Forward logs to WS
-                                log_result = rx.recv() => {
-                                    let log: String = match log_result {
-                                        Ok(content) => content,
-                                        Err(e) => {
-                                            tracing::warn!("Broadcast lag or closed: {}", e);
-                                            break;
-                                        }
-                                    };
-                                    
-                                    if sender.send(Message::Text(log)).await.is_err() {
-                                        break;
-                                    }
-                                }
-                                // Keep reading from WS (ping/pong/close)
-                                ws_msg = receiver.next() => {
-                                    match ws_msg {
-                                        Some(Ok(Message::Close(_))) | None => return,
-                                        _ => {} // Ignore other messages while streaming
-                                    }
-                                }
-                            }
+    // One writer owns the sink; replies and every subscription feed it
+    let (out, mut outgoing) = mpsc::channel::<ServerFrame>(OUTGOING_CAPACITY);
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            let Ok(text) = serde_json::to_string(&frame) else { continue };
+            if sender.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions: HashMap<String, JoinHandle<()>> = HashMap::new();
+    while let Some(Ok(msg)) = receiver.next().await {
+        let text = match msg {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue, // axum answers protocol pings itself
+        };
+        let delivered = match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(message) => handle_message(&state, &out, &mut subscriptions, message).await,
+            Err(e) => out.send(ServerFrame::error(None, None, format!("Invalid message: {}", e))).await.is_ok(),
+        };
+        if !delivered {
+            break;
+        }
+    }
+
+    for task in subscriptions.into_values() {
+        task.abort();
+    }
+    writer.abort();
+}
+
+/// Answers one client request. Returns false once the socket is gone.
+async fn handle_message(
+    state: &AppState,
+    out: &mpsc::Sender<ServerFrame>,
+    subscriptions: &mut HashMap<String, JoinHandle<()>>,
+    message: ClientMessage,
+) -> bool {
+    // Streams that ended on their own don't count anymore
+    subscriptions.retain(|_, task| !task.is_finished());
+
+    let reply = match message {
+        ClientMessage::Ping { id } => ServerFrame::Pong { id },
+        ClientMessage::Subscribe { id, job_id, from_seq } => {
+            if subscriptions.contains_key(&job_id) {
+                ServerFrame::error(id, Some(&job_id), "Already subscribed")
+            } else if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                ServerFrame::error(id, Some(&job_id), format!("At most {} subscriptions per connection", MAX_SUBSCRIPTIONS))
+            } else {
+                match open_stream(state, &job_id, from_seq).await {
+                    Ok(subscription) => {
+                        tracing::info!("WS subscribing to job: {}", job_id);
+                        // Ack first, the stream may start right away
+                        let ack = ServerFrame::Ack { id, request: "subscribe", job_id: job_id.clone() };
+                        if out.send(ack).await.is_err() {
+                            return false;
                         }
-                    } else {
-                        let _ = sender.send(Message::Text("ERROR: Job not found".to_string())).await;
+                        let task = tokio::spawn(forward(state.events.clone(), job_id.clone(), from_seq, subscription, out.clone()));
+                        subscriptions.insert(job_id, task);
+                        return true;
                     }
+                    Err(e) => ServerFrame::error(id, Some(&job_id), e),
                 }
             }
-        } else {
-            break;
         }
+        ClientMessage::Unsubscribe { id, job_id } => match subscriptions.remove(&job_id) {
+            Some(task) => {
+                task.abort();
+                ServerFrame::Ack { id, request: "unsubscribe", job_id }
+            }
+            None => ServerFrame::error(id, Some(&job_id), "Not subscribed"),
+        },
+        ClientMessage::Cancel { id, job_id, control_key } => {
+            match authorize(state, &job_id, control_key).await {
+                Ok(()) => match cancel_job(state, &job_id).await {
+                    Ok(()) => ServerFrame::Ack { id, request: "cancel", job_id },
+                    Err(e) => ServerFrame::error(id, Some(&job_id), e),
+                },
+                Err(e) => ServerFrame::error(id, Some(&job_id), e),
+            }
+        }
+        ClientMessage::Stdin { id, job_id, control_key, data } => {
+            control(state, id, job_id, control_key, "stdin", StdinInput::Data(data)).await
+        }
+        ClientMessage::StdinEof { id, job_id, control_key } => {
+            control(state, id, job_id, control_key, "stdin_eof", StdinInput::Eof).await
+        }
+        ClientMessage::Debug { id, job_id, control_key, request } => {
+            control(state, id, job_id, control_key, "debug", StdinInput::Debug(request)).await
+        }
+    };
+    out.send(reply).await.is_ok()
+}
+
+/// Checks the key a control message came with against the one issued for the job.
+async fn authorize(state: &AppState, job_id: &str, control_key: Option<String>) -> Result<(), String> {
+    let key = control_key.ok_or_else(|| format!("Job {} needs its control_key", job_id))?;
+    if state.jobs.may_control(job_id, &key).await {
+        Ok(())
+    } else {
+        Err(format!("Not allowed to control job {}", job_id))
+    }
+}
+
+/// Writes to a job's stdin once the client is allowed to.
+async fn control(
+    state: &AppState,
+    id: Option<Value>,
+    job_id: String,
+    control_key: Option<String>,
+    request: &'static str,
+    input: StdinInput,
+) -> ServerFrame {
+    let result = match authorize(state, &job_id, control_key).await {
+        Ok(()) => write_stdin(state, &job_id, input).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => ServerFrame::Ack { id, request, job_id },
+        Err(e) => ServerFrame::error(id, Some(&job_id), e),
+    }
+}
+
+pub(super) async fn open_stream(state: &AppState, job_id: &str, from_seq: u64) -> Result<Subscription, String> {
+    if let Some(subscription) = state.events.subscribe(job_id, from_seq).await {
+        return Ok(subscription);
+    }
+    // Detached runs open their stream on submission, so a queued job already
+    // has one; only the executors open streams
+    match state.jobs.get(job_id).await {
+        Some(job) if job.state == JobState::Queued => Err(format!("Job {} is not running", job_id)),
+        Some(_) => Err(format!("Job {} is not running and its events are no longer buffered", job_id)),
+        None => Err(format!("Job {} not found", job_id)),
+    }
+}
+
+/// Relays one job's events to the socket, backlog first, until the job's
+/// stream ends. Falling behind the live channel is repaired from the replay
+/// buffer, so the client sees every seq exactly once unless the buffer
+/// dropped them too.
+pub(super) async fn forward(events: JobEvents, job_id: String, from_seq: u64, mut subscription: Subscription, out: mpsc::Sender<ServerFrame>) {
+    let mut next = from_seq;
+    loop {
+        if subscription.first_seq > next {
+            let skipped = format!("Events {}..{} are no longer buffered", next, subscription.first_seq);
+            if out.send(ServerFrame::error(None, Some(&job_id), skipped)).await.is_err() {
+                return;
+            }
+            next = subscription.first_seq;
+        }
+        for event in std::mem::take(&mut subscription.backlog) {
+            if !send_event(&out, &job_id, &mut next, event).await {
+                return;
+            }
+        }
+
+        if let Some(rx) = subscription.live.as_mut() {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if !send_event(&out, &job_id, &mut next, event).await {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::debug!("WS subscriber of job {} lagged by {} events, replaying", job_id, n);
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+
+        // Catch up from the buffer, or learn that the job is over
+        match events.subscribe(&job_id, next).await {
+            Some(resumed) if resumed.live.is_some() || !resumed.backlog.is_empty() => subscription = resumed,
+            _ => {
+                let _ = out.send(ServerFrame::End { job_id }).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Sends `event` unless it was sent already. Returns false once the socket is gone.
+async fn send_event(out: &mpsc::Sender<ServerFrame>, job_id: &str, next: &mut u64, event: StreamEvent) -> bool {
+    if event.seq < *next {
+        return true;
     }
+    *next = event.seq + 1;
+    let Ok(raw) = RawValue::from_string(event.data) else { return true };
+    out.send(ServerFrame::Event { job_id: job_id.to_string(), seq: event.seq, event: raw }).await.is_ok()
 }
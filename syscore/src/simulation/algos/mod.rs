@@ -122,30 +122,54 @@ impl Scheduler for SRTFScheduler {
     }
 }
 
-pub struct PriorityScheduler;
-impl Scheduler for PriorityScheduler {
-    fn schedule(&self, queue: &[i32], processes: &[Process]) -> Option<i32> {
-        if queue.is_empty() {
-            return None;
-        }
+/// The ready process with the best (lowest) effective priority, earliest arrival on ties.
+fn most_urgent(queue: &[i32], processes: &[Process]) -> Option<i32> {
+    if queue.is_empty() {
+        return None;
+    }
 
-        let mut best_id = queue[0];
-        let mut min_priority = i32::MAX;
-        let mut min_arrival = i32::MAX;
+    let mut best_id = queue[0];
+    let mut min_priority = i32::MAX;
+    let mut min_arrival = i32::MAX;
 
-        for &id in queue {
-            if let Some(p) = processes.iter().find(|p| p.id == id) {
-                if p.priority < min_priority {
-                    min_priority = p.priority;
-                    min_arrival = p.arrival_time;
-                    best_id = id;
-                } else if p.priority == min_priority && p.arrival_time < min_arrival {
-                    min_arrival = p.arrival_time;
-                    best_id = id;
-                }
+    for &id in queue {
+        if let Some(p) = processes.iter().find(|p| p.id == id) {
+            let priority = p.effective_priority();
+            if priority < min_priority {
+                min_priority = priority;
+                min_arrival = p.arrival_time;
+                best_id = id;
+            } else if priority == min_priority && p.arrival_time < min_arrival {
+                min_arrival = p.arrival_time;
+                best_id = id;
             }
         }
-        Some(best_id)
+    }
+    Some(best_id)
+}
+
+/// Non-preemptive: the running process keeps the CPU until it completes.
+pub struct PriorityScheduler;
+impl Scheduler for PriorityScheduler {
+    fn schedule(&self, queue: &[i32], processes: &[Process]) -> Option<i32> {
+        most_urgent(queue, processes)
+    }
+    fn should_preempt(
+        &self,
+        _current: &Process,
+        _queue: &[i32],
+        _processes: &[Process],
+        _quantum_remaining: i32,
+    ) -> bool {
+        false
+    }
+}
+
+/// Preemptive: a ready process with a better effective priority takes over.
+pub struct PreemptivePriorityScheduler;
+impl Scheduler for PreemptivePriorityScheduler {
+    fn schedule(&self, queue: &[i32], processes: &[Process]) -> Option<i32> {
+        most_urgent(queue, processes)
     }
     fn should_preempt(
         &self,
@@ -156,7 +180,7 @@ impl Scheduler for PriorityScheduler {
     ) -> bool {
         for &id in queue {
             if let Some(p) = processes.iter().find(|p| p.id == id) {
-                if p.priority < current.priority {
+                if p.effective_priority() < current.effective_priority() {
                     return true;
                 }
             }
@@ -165,6 +189,36 @@ impl Scheduler for PriorityScheduler {
     }
 }
 
+/// Multi-level feedback queue over the flat ready queue: the lowest
+/// `queue_level` wins, first come first served within a level. Demotion and
+/// the priority boost happen in `next_tick`.
+pub struct MLFQScheduler;
+impl Scheduler for MLFQScheduler {
+    fn schedule(&self, queue: &[i32], processes: &[Process]) -> Option<i32> {
+        let mut best: Option<(i32, i32)> = None;
+        for &id in queue {
+            if let Some(p) = processes.iter().find(|p| p.id == id) {
+                if best.is_none_or(|(_, level)| p.queue_level < level) {
+                    best = Some((id, p.queue_level));
+                }
+            }
+        }
+        best.map(|(id, _)| id)
+    }
+    fn should_preempt(
+        &self,
+        current: &Process,
+        queue: &[i32],
+        processes: &[Process],
+        quantum_remaining: i32,
+    ) -> bool {
+        quantum_remaining < 0
+            || queue.iter().any(|&id| {
+                processes.iter().any(|p| p.id == id && p.queue_level < current.queue_level)
+            })
+    }
+}
+
 pub fn get_scheduler(algo: AlgorithmType) -> Box<dyn Scheduler> {
     match algo {
         AlgorithmType::FCFS => Box::new(FCFSScheduler),
@@ -172,5 +226,7 @@ pub fn get_scheduler(algo: AlgorithmType) -> Box<dyn Scheduler> {
         AlgorithmType::SJF => Box::new(SJFScheduler),
         AlgorithmType::SRTF => Box::new(SRTFScheduler),
         AlgorithmType::PRIORITY => Box::new(PriorityScheduler),
+        AlgorithmType::PRIORITY_P => Box::new(PreemptivePriorityScheduler),
+        AlgorithmType::MLFQ => Box::new(MLFQScheduler),
     }
 }
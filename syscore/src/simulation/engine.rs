@@ -1,169 +1,881 @@
-use crate::simulation::algos::get_scheduler;
-use crate::simulation::types::{AlgorithmType, ProcessState, SimulationState};
+use crate::simulation::algos::{get_scheduler, Scheduler};
+use crate::simulation::types::{
+    AlgorithmType, BurstKind, CoreStats, Device, DeviceGanttBlock, GanttBlock, Process, ProcessState, QueueMode,
+    SimulationState, CONTEXT_SWITCH_PROCESS_ID,
+};
 
 pub fn next_tick(state: SimulationState) -> SimulationState {
+    // MLFQ time slices per level, taken before the state is split up
+    let lowest_level = state.mlfq_lowest_level();
+    let level_quanta: Vec<i32> = (0..=lowest_level).map(|level| state.mlfq_quantum(level)).collect();
+    let level_quantum = |level: i32| level_quanta[level.clamp(0, lowest_level) as usize];
+
     let current_time = state.current_time;
     let mut processes = state.processes;
-    let mut ready_queue = state.ready_queue;
-    let mut running_process_id = state.running_process_id;
     let mut completed_process_ids = state.completed_process_ids;
     let mut gantt_chart = state.gantt_chart;
     let algorithm = state.algorithm;
     let time_quantum = state.time_quantum;
-    let mut quantum_remaining = state.quantum_remaining;
+    let mut devices = state.devices;
+    let mut device_gantt = state.device_gantt;
+
+    // Per-core state. Clients that only know the single-core fields get
+    // them as core 0.
+    let num_cores = state.num_cores.max(1);
+    let n = num_cores as usize;
+    let per_core = state.queue_mode == QueueMode::PerCore;
+    let running = if state.running_process_ids.is_empty() {
+        vec![state.running_process_id]
+    } else {
+        state.running_process_ids
+    };
+    let mut cpu = Cpu {
+        running: Vec::new(),
+        quanta: fit(state.quantum_remaining, n, 0),
+        levels: fit(state.mlfq_current_level, n, 0),
+        stall: fit(state.core_stall, n, 0),
+        cooldown: fit(state.context_switch_cooldown, n, 0),
+        migration_count: state.migration_count,
+        context_switch_count: state.context_switch_count,
+    };
+    let mut core_stats = fit(state.core_stats, n, CoreStats::default());
+    let rules = DispatchRules {
+        algorithm,
+        time_quantum,
+        level_quantum: &level_quantum,
+        migration_cost: state.migration_cost,
+        context_switch_cost: state.context_switch_cost,
+        dispatch_latency: state.dispatch_latency,
+    };
+
+    // Run queues: one per core, or just the shared one. Anything left over
+    // from a core that no longer exists, or not on any queue yet, is placed again.
+    let mut queues: Vec<Vec<i32>> = if per_core { state.core_queues } else { vec![state.ready_queue.clone()] };
+    let mut displaced: Vec<i32> = running.iter().skip(n).flatten().copied().collect();
+    displaced.extend(queues.iter().skip(if per_core { n } else { 1 }).flatten());
+    if per_core {
+        displaced.extend(state.ready_queue.iter().filter(|id| !queues.iter().any(|q| q.contains(id))));
+    }
+    cpu.running = fit(running, n, None);
+    queues = fit(queues, if per_core { n } else { 1 }, Vec::new());
+    for id in displaced {
+        if !cpu.running.contains(&Some(id)) {
+            place(id, None, &mut queues, &cpu.running, &processes, num_cores);
+        }
+    }
+
+    // 0. STARVATION CONTROL
+    // MLFQ: every process periodically goes back to the top level
+    if algorithm == AlgorithmType::MLFQ
+        && state.mlfq_boost_interval > 0
+        && current_time > 0
+        && current_time % state.mlfq_boost_interval == 0
+    {
+        for p in processes.iter_mut() {
+            p.queue_level = 0;
+        }
+    }
+
+    // Priority: ready processes creep up while they wait in a run queue
+    if algorithm.is_priority() && state.priority_aging_enabled && state.priority_aging_interval > 0 {
+        for p in processes.iter_mut() {
+            let waited = current_time - p.ready_since.unwrap_or(p.arrival_time);
+            if p.state == ProcessState::Ready
+                && queues.iter().any(|q| q.contains(&p.id))
+                && waited > 0
+                && waited % state.priority_aging_interval == 0
+            {
+                p.effective_priority = Some((p.effective_priority() - 1).max(0));
+            }
+        }
+    }
 
     // 1. ARRIVAL CHECK
     let mut newly_ready_processes = Vec::new();
     for p in processes.iter_mut() {
         if p.state == ProcessState::Waiting && p.arrival_time <= current_time {
-            p.state = ProcessState::Ready;
-            newly_ready_processes.push(p.id);
+            p.queue_level = 0;
+            if p.bursts.is_empty() && p.remaining_time <= 0 {
+                p.remaining_time = p.burst_time;
+            }
+            if !p.bursts.is_empty() {
+                for burst in p.bursts.iter_mut().filter(|b| b.kind == BurstKind::Cpu) {
+                    burst.duration = burst.duration.max(1);
+                }
+                p.burst_time = p.bursts.iter().filter(|b| b.kind == BurstKind::Cpu).map(|b| b.duration).sum();
+                p.burst_index = 0;
+                if let Some(first) = p.current_burst().filter(|b| !b.is_io()) {
+                    p.remaining_time = first.duration;
+                }
+            }
+            if p.current_burst().is_some_and(|b| b.is_io()) {
+                block_on_io(p, &mut devices);
+            } else {
+                p.state = ProcessState::Ready;
+                newly_ready_processes.push(p.id);
+            }
         }
     }
 
     // Add newly ready processes to the queue
     for id in newly_ready_processes {
-        if !ready_queue.contains(&id) {
-            ready_queue.push(id);
-        }
+        place(id, None, &mut queues, &cpu.running, &processes, num_cores);
     }
 
-    // 2. CPU SCHEDULING (Context Switch / Preemption)
-    let scheduler = get_scheduler(algorithm);
+    // 1b. I/O
+    // Devices serve whoever blocked before this tick; a request that ends
+    // here makes its process ready from the next tick on
+    let any_blocked = processes.iter().any(|p| p.state == ProcessState::Blocked);
+    let mut io_finished = Vec::new();
+    for device in devices.iter_mut() {
+        if device.current_process_id.is_none() && !device.queue.is_empty() {
+            let id = device.queue.remove(0);
+            let duration = processes.iter()
+                .find(|p| p.id == id)
+                .and_then(|p| p.current_burst())
+                .map_or(0, |b| b.duration);
+            device.current_process_id = Some(id);
+            device.remaining = if duration > 0 { duration } else { device.service_time.max(1) };
+        }
+        let Some(id) = device.current_process_id else { continue };
 
-    if let Some(active_id) = running_process_id {
-        if algorithm == AlgorithmType::RR {
-            quantum_remaining -= 1;
-            let current_proc = processes.iter().find(|p| p.id == active_id);
-            if let Some(p) = current_proc {
-                if scheduler.should_preempt(p, &ready_queue, &processes, quantum_remaining) {
-                    if p.state != ProcessState::Completed {
-                        ready_queue.push(active_id);
-                        running_process_id = None;
-                    }
+        device.busy_time += 1;
+        device.remaining -= 1;
+        match device_gantt.iter_mut().rev().find(|b| b.device_id == device.id) {
+            Some(last) if last.process_id == id && last.end_time == current_time => last.end_time = current_time + 1,
+            _ => device_gantt.push(DeviceGanttBlock {
+                device_id: device.id,
+                process_id: id,
+                start_time: current_time,
+                end_time: current_time + 1,
+            }),
+        }
+        if device.remaining <= 0 {
+            device.current_process_id = None;
+            device.remaining = 0;
+            io_finished.push(id);
+        }
+    }
+    for p in processes.iter_mut().filter(|p| p.state == ProcessState::Blocked) {
+        p.io_time += 1;
+    }
+
+    // 1c. LOAD BALANCING (per-core queues only)
+    if per_core && n > 1 {
+        // Push: periodically even out the queues
+        if state.balance_interval > 0 && current_time > 0 && current_time % state.balance_interval == 0 {
+            for _ in 0..processes.len() {
+                let load = |c: usize| queues[c].len() + cpu.running[c].is_some() as usize;
+                let busiest = (0..n).max_by_key(|&c| (load(c), std::cmp::Reverse(c))).unwrap_or(0);
+                let idlest = (0..n).min_by_key(|&c| (load(c), c)).unwrap_or(0);
+                if load(busiest) <= load(idlest) + 1 || !steal(busiest, idlest, &mut queues, &processes, num_cores) {
+                    break;
                 }
             }
-        } else {
-            let current_proc = processes.iter().find(|p| p.id == active_id);
-            if let Some(p) = current_proc {
-                if scheduler.should_preempt(p, &ready_queue, &processes, quantum_remaining) {
-                    if !ready_queue.contains(&active_id) {
-                        ready_queue.push(active_id);
+        }
+        // Pull: a core with nothing to do takes work from the busiest queue,
+        // but not the only job of a core that is idle too and about to run it
+        if state.idle_pull {
+            for core in 0..n {
+                if cpu.running[core].is_some() || !queues[core].is_empty() {
+                    continue;
+                }
+                let load = |c: usize| queues[c].len() + cpu.running[c].is_some() as usize;
+                let mut sources: Vec<usize> = (0..n)
+                    .filter(|&c| c != core && !queues[c].is_empty() && (cpu.running[c].is_some() || load(c) > 1))
+                    .collect();
+                sources.sort_by_key(|&c| std::cmp::Reverse(load(c)));
+                for source in sources {
+                    if steal(source, core, &mut queues, &processes, num_cores) {
+                        break;
                     }
-                    running_process_id = None;
                 }
             }
         }
     }
 
-    // Selection Logic (If CPU Free)
-    if running_process_id.is_none() && !ready_queue.is_empty() {
-        if let Some(next_id) = scheduler.schedule(&ready_queue, &processes) {
-            if let Some(pos) = ready_queue.iter().position(|&id| id == next_id) {
-                ready_queue.remove(pos);
+    // 2. CPU SCHEDULING (Context Switch / Preemption)
+    // Idle cores take work first, so a newcomer lands on a free core rather
+    // than preempting a busy one. Then each busy core checks for preemption
+    // and, if it gave up its process, picks again right away. Cores in the
+    // middle of a context switch or a migration warm-up are left alone.
+    let scheduler = get_scheduler(algorithm);
+    let queue_of = |core: usize| if per_core { core } else { 0 };
+    let was_running = cpu.running.clone();
+
+    for core in 0..n {
+        if cpu.running[core].is_none() && !cpu.is_stalled(core) {
+            // Whoever left this core at the end of the last tick
+            let previous = gantt_chart.iter()
+                .rev()
+                .find(|b| b.core_id == core as i32)
+                .filter(|b| b.end_time == current_time)
+                .and_then(|b| b.process_id)
+                .filter(|&id| id != CONTEXT_SWITCH_PROCESS_ID);
+            let picked = dispatch(core, scheduler.as_ref(), &mut queues[queue_of(core)], &mut processes, num_cores);
+            if let Some((next_id, migrated)) = picked {
+                start_slice(core, next_id, previous, migrated, &rules, &mut processes, &mut cpu);
+            }
+        }
+    }
+
+    for core in 0..n {
+        let Some(active_id) = was_running[core] else { continue };
+        if cpu.running[core] != Some(active_id) || cpu.is_stalled(core) {
+            continue;
+        }
+        if algorithm.is_time_sliced() {
+            cpu.quanta[core] -= 1;
+        }
+        let visible = visible_queue(&queues[queue_of(core)], core, &processes, num_cores);
+        let preempt = processes.iter()
+            .find(|p| p.id == active_id)
+            .is_some_and(|p| {
+                scheduler.should_preempt(p, &visible, &processes, cpu.quanta[core])
+                    && p.state != ProcessState::Completed
+            });
+        if preempt {
+            // MLFQ: using up the whole slice costs a level, being preempted from above doesn't
+            if let Some(p) = processes.iter_mut().find(|p| p.id == active_id) {
+                if algorithm == AlgorithmType::MLFQ && cpu.quanta[core] < 0 {
+                    p.queue_level = (p.queue_level + 1).min(lowest_level);
+                }
+                p.core_id = None;
             }
-            running_process_id = Some(next_id);
+            cpu.running[core] = None;
+            place(active_id, Some(core), &mut queues, &cpu.running, &processes, num_cores);
 
-            if algorithm == AlgorithmType::RR {
-                quantum_remaining = time_quantum - 1;
+            let picked = dispatch(core, scheduler.as_ref(), &mut queues[queue_of(core)], &mut processes, num_cores);
+            if let Some((next_id, migrated)) = picked {
+                start_slice(core, next_id, Some(active_id), migrated, &rules, &mut processes, &mut cpu);
             }
         }
     }
 
     // 3. EXECUTION
-    let mut active_id_to_null = false;
-    let mut runner_id: Option<i32> = None;
-
-    if let Some(active_id) = running_process_id {
-        runner_id = Some(active_id);
-        if let Some(p) = processes.iter_mut().find(|p| p.id == active_id) {
-            let next_remaining = p.remaining_time - 1;
-            if p.start_time.is_none() {
-                p.start_time = Some(current_time);
-            }
+    // Idle cores count while anything is left at the start of the tick
+    let has_pending = processes.iter().any(|p| p.state != ProcessState::Completed);
+    let mut runner_ids: Vec<Option<i32>> = vec![None; n];
+
+    for (core, runner_id) in runner_ids.iter_mut().enumerate() {
+        let Some(active_id) = cpu.running[core] else { continue };
+        let Some(p) = processes.iter_mut().find(|p| p.id == active_id) else { continue };
+        p.state = ProcessState::Running;
+        p.core_id = Some(core as i32);
+
+        // Context switch: the core works for the kernel, not the process
+        if cpu.cooldown[core] > 0 {
+            cpu.cooldown[core] -= 1;
+            *runner_id = Some(CONTEXT_SWITCH_PROCESS_ID);
+            continue;
+        }
 
-            if next_remaining <= 0 {
-                // Completed
-                let finish_time = current_time + 1;
-                let tat = finish_time - p.arrival_time;
-                let wt = tat - p.burst_time;
+        *runner_id = Some(active_id);
+        if p.start_time.is_none() {
+            p.start_time = Some(current_time);
+        }
 
-                p.state = ProcessState::Completed;
-                p.remaining_time = 0;
-                p.completion_time = Some(finish_time);
-                p.turnaround_time = tat;
-                p.waiting_time = wt;
+        // Migration warm-up: the core is taken but the process doesn't advance
+        if cpu.stall[core] > 0 {
+            cpu.stall[core] -= 1;
+            p.migration_time += 1;
+            continue;
+        }
 
-                if !completed_process_ids.contains(&p.id) {
-                    completed_process_ids.push(p.id);
+        let next_remaining = p.remaining_time - 1;
+        if next_remaining <= 0 {
+            // CPU burst done: on to the next burst, or completed
+            p.burst_index += 1;
+            match p.current_burst().map(|b| (b.kind, b.duration)) {
+                Some((BurstKind::Cpu, duration)) => {
+                    p.remaining_time = duration.max(1);
+                }
+                Some((BurstKind::Io, _)) => {
+                    p.remaining_time = 0;
+                    p.core_id = None;
+                    block_on_io(p, &mut devices);
+                    cpu.running[core] = None;
+                }
+                None => {
+                    p.core_id = None;
+                    complete(p, current_time + 1);
+                    if !completed_process_ids.contains(&p.id) {
+                        completed_process_ids.push(p.id);
+                    }
+                    cpu.running[core] = None;
                 }
-                active_id_to_null = true;
-            } else {
-                p.state = ProcessState::Running;
-                p.remaining_time = next_remaining;
             }
+        } else {
+            p.remaining_time = next_remaining;
         }
     }
 
-    if active_id_to_null {
-        running_process_id = None;
+    // Processes whose I/O ended this tick go back to the core they last ran on
+    for id in io_finished {
+        let Some(p) = processes.iter_mut().find(|p| p.id == id) else { continue };
+        p.burst_index += 1;
+        match p.current_burst().map(|b| (b.kind, b.duration)) {
+            Some((BurstKind::Cpu, duration)) => {
+                p.state = ProcessState::Ready;
+                p.remaining_time = duration.max(1);
+                p.ready_since = Some(current_time + 1);
+                let hint = p.last_core.map(|c| c as usize);
+                place(id, hint, &mut queues, &cpu.running, &processes, num_cores);
+            }
+            Some((BurstKind::Io, _)) => block_on_io(p, &mut devices),
+            None => {
+                complete(p, current_time + 1);
+                if !completed_process_ids.contains(&id) {
+                    completed_process_ids.push(id);
+                }
+            }
+        }
     }
 
     // Update other processes states
     for p in processes.iter_mut() {
-        if running_process_id != Some(p.id) && ready_queue.contains(&p.id) {
+        if !cpu.running.contains(&Some(p.id)) && queues.iter().any(|q| q.contains(&p.id)) {
             p.state = ProcessState::Ready;
+            p.core_id = None;
+            p.ready_since.get_or_insert(current_time);
         }
     }
 
     // 4. GANTT CHART UPDATE
-    if let Some(rid) = runner_id {
-        let mut appended = false;
-        if let Some(last) = gantt_chart.last_mut() {
-            if last.process_id == Some(rid) && last.end_time == current_time {
-                last.end_time = current_time + 1;
-                appended = true;
+    let mut cpu_busy_time = state.cpu_busy_time;
+    let mut cpu_idle_time = state.cpu_idle_time;
+    let mut cpu_idle_blocked_time = state.cpu_idle_blocked_time;
+    let mut context_switch_time_wasted = state.context_switch_time_wasted;
+    for (core, runner_id) in runner_ids.into_iter().enumerate() {
+        if runner_id == Some(CONTEXT_SWITCH_PROCESS_ID) {
+            context_switch_time_wasted += 1;
+            core_stats[core].switch_time += 1;
+        } else if runner_id.is_some() {
+            cpu_busy_time += 1;
+            core_stats[core].busy_time += 1;
+        } else if has_pending {
+            // IDLE
+            cpu_idle_time += 1;
+            core_stats[core].idle_time += 1;
+            if any_blocked {
+                cpu_idle_blocked_time += 1;
             }
+        } else {
+            continue;
         }
-        if !appended {
-            gantt_chart.push(crate::simulation::types::GanttBlock {
-                process_id: Some(rid),
+        let core_id = core as i32;
+        match gantt_chart.iter_mut().rev().find(|b| b.core_id == core_id) {
+            Some(last) if last.process_id == runner_id && last.end_time == current_time => {
+                last.end_time = current_time + 1;
+            }
+            _ => gantt_chart.push(GanttBlock {
+                process_id: runner_id,
                 start_time: current_time,
                 end_time: current_time + 1,
-            });
+                core_id,
+            }),
         }
-    } else {
-        // IDLE
-        let has_pending = processes.iter().any(|p| p.state != ProcessState::Completed);
-        if has_pending {
-            let mut appended = false;
-            if let Some(last) = gantt_chart.last_mut() {
-                if last.process_id.is_none() && last.end_time == current_time {
-                    last.end_time = current_time + 1;
-                    appended = true;
-                }
-            }
-            if !appended {
-                gantt_chart.push(crate::simulation::types::GanttBlock {
-                    process_id: None,
-                    start_time: current_time,
-                    end_time: current_time + 1,
-                });
+    }
+    for stats in core_stats.iter_mut() {
+        let working = stats.busy_time + stats.switch_time;
+        stats.utilization = ratio(working, working + stats.idle_time);
+    }
+
+    for p in processes.iter_mut() {
+        p.effective_priority.get_or_insert(p.priority);
+    }
+
+    let ready_queue: Vec<i32> = queues.concat();
+
+    // 5. MLFQ QUEUES (the per-level view of the ready queue)
+    let mlfq_queues = if algorithm == AlgorithmType::MLFQ {
+        let mut queues = vec![Vec::new(); lowest_level as usize + 1];
+        for &id in &ready_queue {
+            if let Some(p) = processes.iter().find(|p| p.id == id) {
+                queues[p.queue_level.clamp(0, lowest_level) as usize].push(id);
             }
         }
-    }
+        queues
+    } else {
+        state.mlfq_queues
+    };
+
+    // Switching keeps the CPU from idling but gets no process anywhere
+    let working = cpu_busy_time + context_switch_time_wasted;
+    let cpu_utilization = ratio(working, working + cpu_idle_time);
+    let effective_cpu_utilization = ratio(cpu_busy_time, working + cpu_idle_time);
+    let context_switch_overhead = ratio(context_switch_time_wasted, working);
 
     SimulationState {
         current_time: current_time + 1,
         processes,
         ready_queue,
-        running_process_id,
+        running_process_id: cpu.running[0],
         completed_process_ids,
         gantt_chart,
         algorithm,
         time_quantum,
-        quantum_remaining,
-        is_playing: state.is_playing,
-        speed: state.speed,
+        quantum_remaining: cpu.quanta,
+        mlfq_queues,
+        mlfq_current_level: cpu.levels,
+        devices,
+        device_gantt,
+        cpu_busy_time,
+        cpu_idle_time,
+        cpu_idle_blocked_time,
+        cpu_utilization,
+        num_cores,
+        running_process_ids: cpu.running,
+        core_queues: if per_core { queues } else { Vec::new() },
+        core_stall: cpu.stall,
+        core_stats,
+        migration_count: cpu.migration_count,
+        context_switch_count: cpu.context_switch_count,
+        context_switch_time_wasted,
+        context_switch_cooldown: cpu.cooldown,
+        context_switch_overhead,
+        effective_cpu_utilization,
+        ..state
+    }
+}
+
+/// Pads or truncates a per-core list to `n` entries.
+fn fit<T: Clone>(mut values: Vec<T>, n: usize, fill: T) -> Vec<T> {
+    values.resize(n, fill);
+    values
+}
+
+fn ratio(part: i32, whole: i32) -> f64 {
+    if whole > 0 { part as f64 / whole as f64 } else { 0.0 }
+}
+
+/// Puts a ready process on a run queue. With per-core queues it goes to
+/// `core` if it may run there, else to the least loaded core it may run on.
+fn place(id: i32, core: Option<usize>, queues: &mut [Vec<i32>], running: &[Option<i32>], processes: &[Process], num_cores: i32) {
+    if queues.iter().any(|q| q.contains(&id)) {
+        return;
+    }
+    let runs_on = |c: usize| processes.iter().find(|p| p.id == id).is_none_or(|p| p.runs_on(c as i32, num_cores));
+    let target = if queues.len() == 1 {
+        0
+    } else {
+        core.filter(|&c| c < queues.len() && runs_on(c)).unwrap_or_else(|| {
+            (0..queues.len())
+                .filter(|&c| runs_on(c))
+                .min_by_key(|&c| (queues[c].len() + running[c].is_some() as usize, c))
+                .unwrap_or(0)
+        })
+    };
+    queues[target].push(id);
+}
+
+/// Moves the last process on `from`'s queue that may run on `to`.
+fn steal(from: usize, to: usize, queues: &mut [Vec<i32>], processes: &[Process], num_cores: i32) -> bool {
+    let movable = queues[from].iter().rposition(|&id| {
+        processes.iter().find(|p| p.id == id).is_some_and(|p| p.runs_on(to as i32, num_cores))
+    });
+    let Some(pos) = movable else { return false };
+    let id = queues[from].remove(pos);
+    queues[to].push(id);
+    true
+}
+
+/// The part of a run queue `core` may take work from.
+fn visible_queue(queue: &[i32], core: usize, processes: &[Process], num_cores: i32) -> Vec<i32> {
+    queue.iter()
+        .copied()
+        .filter(|&id| processes.iter().find(|p| p.id == id).is_some_and(|p| p.runs_on(core as i32, num_cores)))
+        .collect()
+}
+
+/// Lets the scheduler pick from what `core` may run and takes the pick off
+/// the queue. Returns it and whether it came from another core.
+fn dispatch(core: usize, scheduler: &dyn Scheduler, queue: &mut Vec<i32>, processes: &mut [Process], num_cores: i32) -> Option<(i32, bool)> {
+    let visible = visible_queue(queue, core, processes, num_cores);
+    let next_id = scheduler.schedule(&visible, processes)?;
+    queue.retain(|&id| id != next_id);
+
+    let p = processes.iter_mut().find(|p| p.id == next_id)?;
+    // Leaving the queue spends what aging earned
+    p.effective_priority = Some(p.priority);
+    p.ready_since = None;
+    let core = core as i32;
+    let migrated = p.last_core.is_some_and(|last| last != core);
+    if migrated {
+        p.migrations += 1;
+    }
+    p.last_core = Some(core);
+    p.core_id = Some(core);
+    Some((next_id, migrated))
+}
+
+/// What each core is doing, beyond the run queues.
+struct Cpu {
+    running: Vec<Option<i32>>,
+    quanta: Vec<i32>,
+    /// MLFQ level of each core's process
+    levels: Vec<i32>,
+    /// Migration warm-up ticks left
+    stall: Vec<i32>,
+    /// Context switch ticks left
+    cooldown: Vec<i32>,
+    migration_count: i32,
+    context_switch_count: i32,
+}
+
+impl Cpu {
+    fn is_stalled(&self, core: usize) -> bool {
+        self.stall[core] > 0 || self.cooldown[core] > 0
+    }
+}
+
+/// How long a slice lasts and what getting onto a core costs.
+struct DispatchRules<'a> {
+    algorithm: AlgorithmType,
+    time_quantum: i32,
+    level_quantum: &'a dyn Fn(i32) -> i32,
+    migration_cost: i32,
+    context_switch_cost: i32,
+    dispatch_latency: i32,
+}
+
+/// Puts a freshly dispatched process on `core`: its time slice, its MLFQ
+/// level, and the overhead of getting there. Taking over from a different
+/// process is a context switch; every new process pays the dispatch
+/// latency; coming from another core adds the migration warm-up.
+fn start_slice(
+    core: usize,
+    next_id: i32,
+    previous: Option<i32>,
+    migrated: bool,
+    rules: &DispatchRules,
+    processes: &mut [Process],
+    cpu: &mut Cpu,
+) {
+    cpu.running[core] = Some(next_id);
+    let Some(p) = processes.iter_mut().find(|p| p.id == next_id) else { return };
+    if rules.algorithm == AlgorithmType::RR {
+        cpu.quanta[core] = rules.time_quantum - 1;
+    } else if rules.algorithm == AlgorithmType::MLFQ {
+        cpu.quanta[core] = (rules.level_quantum)(p.queue_level) - 1;
+        cpu.levels[core] = p.queue_level;
+    }
+
+    if previous.is_some_and(|prev| prev != next_id) {
+        cpu.context_switch_count += 1;
+        p.context_switches += 1;
+        cpu.cooldown[core] = rules.context_switch_cost.max(0);
+    }
+    if previous != Some(next_id) {
+        cpu.cooldown[core] += rules.dispatch_latency.max(0);
+    }
+    if migrated {
+        cpu.migration_count += 1;
+        cpu.stall[core] = rules.migration_cost.max(0);
+    }
+    if cpu.is_stalled(core) {
+        // The slice starts once the process actually runs
+        cpu.quanta[core] += 1;
+    }
+}
+
+/// Queues a process on the device its current burst names, adding the
+/// device if it isn't configured.
+fn block_on_io(p: &mut Process, devices: &mut Vec<Device>) {
+    let device_id = p.current_burst().map_or(0, |b| b.device);
+    let device = match devices.iter().position(|d| d.id == device_id) {
+        Some(i) => &mut devices[i],
+        None => {
+            devices.push(Device::new(device_id));
+            devices.last_mut().expect("just pushed")
+        }
+    };
+    if !device.queue.contains(&p.id) {
+        device.queue.push(p.id);
+    }
+    p.state = ProcessState::Blocked;
+}
+
+/// Final stats. Waiting time is time spent ready, so blocked time and
+/// migration warm-up don't count.
+fn complete(p: &mut Process, finish_time: i32) {
+    let tat = finish_time - p.arrival_time;
+    p.state = ProcessState::Completed;
+    p.remaining_time = 0;
+    p.completion_time = Some(finish_time);
+    p.turnaround_time = tat;
+    p.waiting_time = tat - p.burst_time - p.io_time - p.migration_time;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::testing::*;
+    use crate::simulation::types::{ProcessState, CONTEXT_SWITCH_PROCESS_ID};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn fcfs_runs_in_arrival_order() {
+        let state = run(simulation("FCFS", staggered(), json!({})));
+        assert_eq!(timeline(&state), vec![1, 1, 1, 2, 2, 3]);
+        assert_eq!(process(&state, 3).waiting_time, 3);
+        assert_eq!(process(&state, 3).turnaround_time, 4);
+    }
+
+    #[test]
+    fn sjf_picks_the_shortest_once_the_cpu_is_free() {
+        let state = run(simulation("SJF", staggered(), json!({})));
+        assert_eq!(timeline(&state), vec![1, 1, 1, 3, 2, 2]);
+    }
+
+    #[test]
+    fn srtf_preempts_for_a_shorter_arrival() {
+        let state = run(simulation("SRTF", vec![job(1, 5, 0), job(2, 2, 1)], json!({})));
+        assert_eq!(timeline(&state), vec![1, 2, 2, 1, 1, 1, 1]);
+        assert_eq!(process(&state, 1).completion_time, Some(7));
+    }
+
+    #[test]
+    fn rr_requeues_behind_arrivals() {
+        let state = run(simulation("RR", staggered(), json!({ "timeQuantum": 2 })));
+        assert_eq!(timeline(&state), vec![1, 1, 2, 2, 3, 1]);
+    }
+
+    #[test]
+    fn mlfq_demotes_after_a_full_slice() {
+        let settings = json!({ "mlfqQuantums": [1, 2], "mlfqNumQueues": 2 });
+        let state = run(simulation("MLFQ", vec![job(1, 5, 0), job(2, 5, 0)], settings));
+        assert_eq!(timeline(&state), vec![1, 2, 1, 1, 2, 2, 1, 1, 2, 2]);
+        assert!(state.processes.iter().all(|p| p.queue_level == 1));
+    }
+
+    #[test]
+    fn mlfq_preemption_from_above_keeps_the_level() {
+        let settings = json!({ "mlfqQuantums": [1, 4] });
+        let state = run(simulation("MLFQ", vec![job(1, 6, 0), job(2, 1, 3)], settings));
+        assert_eq!(timeline(&state), vec![1, 1, 1, 2, 1, 1, 1]);
+        assert_eq!(process(&state, 1).queue_level, 1);
+        assert_eq!(process(&state, 2).queue_level, 0);
+    }
+
+    #[test]
+    fn mlfq_boost_returns_everyone_to_the_top() {
+        let settings = json!({ "mlfqQuantums": [1, 2, 8], "mlfqBoostInterval": 4 });
+        let state = ticks(simulation("MLFQ", vec![job(1, 9, 0)], settings), 4);
+        assert_eq!(state.processes[0].queue_level, 2);
+        let state = ticks(state, 1);
+        assert_eq!(state.processes[0].queue_level, 0);
+    }
+
+    fn prioritized() -> Vec<Value> {
+        vec![
+            with(job(1, 3, 0), json!({ "priority": 2 })),
+            with(job(2, 2, 1), json!({ "priority": 1 })),
+            with(job(3, 1, 1), json!({ "priority": 0 })),
+        ]
+    }
+
+    #[test]
+    fn priority_waits_for_the_running_process() {
+        let state = run(simulation("PRIORITY", prioritized(), json!({})));
+        assert_eq!(timeline(&state), vec![1, 1, 1, 3, 2, 2]);
+    }
+
+    #[test]
+    fn preemptive_priority_takes_the_cpu() {
+        let state = run(simulation("PRIORITY_P", prioritized(), json!({})));
+        assert_eq!(timeline(&state), vec![1, 3, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn aging_lifts_a_waiting_process() {
+        let processes = vec![job(1, 6, 0), with(job(2, 1, 0), json!({ "priority": 3 }))];
+        let settings = json!({ "priorityAgingEnabled": true, "priorityAgingInterval": 2 });
+        let state = ticks(simulation("PRIORITY", processes, settings), 5);
+        assert_eq!(process(&state, 2).effective_priority, Some(1));
+        assert_eq!(process(&state, 2).priority, 3);
+    }
+
+    #[test]
+    fn io_burst_blocks_and_frees_the_cpu() {
+        let state = run(simulation("FCFS", vec![io_job(1, 2, 1, 3), job(2, 2, 0)], json!({})));
+
+        assert_eq!(timeline(&state), vec![1, 1, 2, 2, 0, 1]);
+        let served: Vec<(i32, i32)> = state.device_gantt.iter().map(|b| (b.start_time, b.end_time)).collect();
+        assert_eq!(served, vec![(2, 5)]);
+
+        let p1 = process(&state, 1);
+        assert_eq!((p1.burst_time, p1.io_time, p1.waiting_time, p1.turnaround_time), (3, 3, 0, 6));
+        assert_eq!(state.cpu_idle_time, 1);
+        assert_eq!(state.cpu_idle_blocked_time, 1);
+    }
+
+    #[test]
+    fn io_is_ready_from_the_next_tick() {
+        let state = ticks(simulation("FCFS", vec![io_job(1, 1, 1, 2)], json!({})), 2);
+        assert_eq!(state.processes[0].state, ProcessState::Blocked);
+        let state = ticks(state, 1);
+        assert_eq!(state.processes[0].state, ProcessState::Ready);
+        let state = ticks(state, 1);
+        assert_eq!(state.processes[0].state, ProcessState::Completed);
+    }
+
+    #[test]
+    fn a_device_serves_one_request_at_a_time() {
+        let state = run(simulation("FCFS", vec![io_job(1, 1, 0, 2), io_job(2, 1, 0, 2)], json!({})));
+
+        assert_eq!(timeline(&state), vec![1, 2, 0, 1, 0, 2]);
+        let served: Vec<(i32, i32, i32)> = state.device_gantt.iter()
+            .map(|b| (b.process_id, b.start_time, b.end_time))
+            .collect();
+        assert_eq!(served, vec![(1, 1, 3), (2, 3, 5)]);
+        assert_eq!(process(&state, 2).io_time, 3);
+    }
+
+    fn two_cores(queue_mode: &str) -> Value {
+        json!({ "numCores": 2, "queueMode": queue_mode })
+    }
+
+    #[test]
+    fn global_queue_feeds_whichever_core_is_free() {
+        let processes = vec![job(1, 2, 0), job(2, 3, 0), job(3, 1, 0), job(4, 2, 0)];
+        let state = run(simulation("FCFS", processes, two_cores("GLOBAL")));
+        assert_eq!(core_timeline(&state, 0), vec![1, 1, 3, 4, 4]);
+        assert_eq!(core_timeline(&state, 1), vec![2, 2, 2, 0, 0]);
+        assert_eq!((state.core_stats[1].busy_time, state.core_stats[1].idle_time), (3, 2));
+        assert_eq!(state.cpu_busy_time, 8);
+    }
+
+    #[test]
+    fn per_core_queues_keep_their_work() {
+        let processes = vec![job(1, 2, 0), job(2, 3, 0), job(3, 1, 0), job(4, 2, 0)];
+        let state = run(simulation("FCFS", processes, two_cores("PER_CORE")));
+        assert_eq!(core_timeline(&state, 0), vec![1, 1, 3, 0, 0]);
+        assert_eq!(core_timeline(&state, 1), vec![2, 2, 2, 4, 4]);
+    }
+
+    #[test]
+    fn affinity_is_respected() {
+        let pinned = |id| with(job(id, 2, 0), json!({ "affinity": [1] }));
+        let state = run(simulation("FCFS", vec![pinned(1), pinned(2)], two_cores("GLOBAL")));
+        assert_eq!(core_timeline(&state, 0), vec![0, 0, 0, 0]);
+        assert_eq!(core_timeline(&state, 1), vec![1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn push_balancing_evens_out_the_queues() {
+        let settings = with(two_cores("PER_CORE"), json!({
+            "balanceInterval": 1, "coreQueues": [[1, 2, 3], []], "currentTime": 1
+        }));
+        let state = run(simulation("FCFS", vec![ready(1, 3), ready(2, 3), ready(3, 3)], settings));
+        assert_eq!(core_timeline(&state, 1), vec![3, 3, 3, 0, 0, 0]);
+        // It never ran anywhere before, so moving it is no migration
+        assert_eq!(state.migration_count, 0);
+    }
+
+    #[test]
+    fn migration_costs_a_warm_up() {
+        let moved = with(ready(1, 2), json!({ "lastCore": 0 }));
+        let settings = with(two_cores("PER_CORE"), json!({ "migrationCost": 2, "coreQueues": [[], [1]] }));
+        let state = run(simulation("FCFS", vec![moved], settings));
+
+        assert_eq!(core_timeline(&state, 1), vec![1, 1, 1, 1]);
+        assert_eq!(state.migration_count, 1);
+        let p = &state.processes[0];
+        assert_eq!((p.migrations, p.migration_time, p.waiting_time), (1, 2, 0));
+    }
+
+    #[test]
+    fn io_returns_to_the_last_core() {
+        let processes = vec![io_job(1, 1, 0, 1), job(2, 1, 0), job(3, 3, 1)];
+        let state = run(simulation("FCFS", processes, two_cores("PER_CORE")));
+        assert_eq!(core_timeline(&state, 0), vec![1, 3, 3, 3, 1]);
+        assert_eq!(state.migration_count, 0);
+    }
+
+    const SWITCH: i32 = CONTEXT_SWITCH_PROCESS_ID;
+
+    #[test]
+    fn switching_processes_costs_ticks() {
+        let state = run(simulation("FCFS", vec![job(1, 2, 0), job(2, 2, 0)], json!({ "contextSwitchCost": 1 })));
+
+        assert_eq!(timeline(&state), vec![1, 1, SWITCH, 2, 2]);
+        assert_eq!((state.context_switch_count, state.context_switch_time_wasted), (1, 1));
+        assert_eq!(process(&state, 2).context_switches, 1);
+        assert_eq!(state.cpu_utilization, 1.0);
+        assert_eq!(state.effective_cpu_utilization, 0.8);
+        assert_eq!(state.context_switch_overhead, 0.2);
+    }
+
+    #[test]
+    fn dispatch_latency_applies_to_every_new_process() {
+        let state = run(simulation("FCFS", vec![job(1, 2, 0), job(2, 2, 0)], json!({ "dispatchLatency": 1 })));
+        assert_eq!(timeline(&state), vec![SWITCH, 1, 1, SWITCH, 2, 2]);
+        assert_eq!((state.context_switch_count, state.context_switch_time_wasted), (1, 2));
+    }
+
+    #[test]
+    fn rr_pays_for_every_slice_switch() {
+        let settings = json!({ "contextSwitchCost": 1, "timeQuantum": 1 });
+        let state = run(simulation("RR", vec![job(1, 2, 0), job(2, 2, 0)], settings));
+        assert_eq!(timeline(&state), vec![1, SWITCH, 2, SWITCH, 1, SWITCH, 2]);
+        assert_eq!(state.context_switch_count, 3);
+    }
+
+    #[test]
+    fn running_on_alone_is_not_a_switch() {
+        let settings = json!({ "contextSwitchCost": 2, "timeQuantum": 1 });
+        let state = run(simulation("RR", vec![job(1, 3, 0)], settings));
+        assert_eq!(timeline(&state), vec![1, 1, 1]);
+        assert_eq!(state.context_switch_count, 0);
+    }
+
+    #[test]
+    fn idle_pull_leaves_an_idle_cores_only_job_alone() {
+        let settings = with(two_cores("PER_CORE"), json!({ "idlePull": true }));
+        let state = ticks(simulation("FCFS", vec![job(1, 3, 0)], settings), 4);
+
+        assert_eq!(state.migration_count, 0);
+        assert_eq!(core_timeline(&state, 0), vec![1, 1, 1]);
+        assert!(core_timeline(&state, 1).iter().all(|&id| id == 0));
+        assert_eq!(process(&state, 1).completion_time, Some(3));
+    }
+
+    #[test]
+    fn idle_pull_takes_work_from_a_loaded_core() {
+        let settings = with(two_cores("PER_CORE"), json!({ "idlePull": true, "coreQueues": [[1, 2], []] }));
+        let state = ticks(simulation("FCFS", vec![ready(1, 4), ready(2, 4)], settings), 1);
+        assert_eq!(state.running_process_ids, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn aging_counts_from_joining_the_queue() {
+        // P1 is preempted at t=1, so it ages at t=3, not at t=2 and t=4
+        let processes = vec![
+            with(job(1, 4, 0), json!({ "priority": 2 })),
+            with(job(2, 3, 1), json!({ "priority": 0 })),
+        ];
+        let settings = json!({ "priorityAgingEnabled": true, "priorityAgingInterval": 2 });
+        let state = ticks(simulation("PRIORITY_P", processes, settings), 3);
+        assert_eq!(process(&state, 1).ready_since, Some(1));
+        assert_eq!(process(&state, 1).effective_priority, Some(2));
+
+        let state = ticks(state, 1);
+        assert_eq!(process(&state, 1).effective_priority, Some(1));
+    }
+
+    #[test]
+    fn dispatch_spends_the_aging_boost() {
+        let processes = vec![
+            with(job(1, 4, 0), json!({ "priority": 0 })),
+            with(job(2, 2, 0), json!({ "priority": 3 })),
+        ];
+        let settings = json!({ "priorityAgingEnabled": true, "priorityAgingInterval": 1 });
+        let state = ticks(simulation("PRIORITY", processes, settings), 4);
+        assert_eq!(process(&state, 2).effective_priority, Some(0));
+
+        let state = ticks(state, 1);
+        assert_eq!(state.running_process_id, Some(2));
+        assert_eq!(process(&state, 2).effective_priority, Some(3));
+        assert_eq!(process(&state, 2).ready_since, None);
     }
 }
@@ -1,6 +1,10 @@
 pub mod types;
 pub mod algos;
 pub mod engine;
+pub mod run;
+#[cfg(test)]
+mod testing;
 
 pub use types::*;
 pub use engine::next_tick;
+pub use run::{run_to_completion, SimulationReport, SimulationRunRequest};
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use crate::simulation::engine::next_tick;
+use crate::simulation::types::{CoreStats, DeviceGanttBlock, GanttBlock, Process, ProcessState, SimulationState};
+
+pub const DEFAULT_MAX_TICKS: i32 = 10_000;
+pub const MAX_TICKS_LIMIT: i32 = 100_000;
+/// Every tick scans the processes, so a run's cost grows with both limits
+pub const MAX_PROCESSES: usize = 500;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationRunRequest {
+    /// The workload and algorithm settings, as a starting `SimulationState`
+    #[serde(flatten)]
+    pub state: SimulationState,
+    /// Safety cap on the number of ticks, `DEFAULT_MAX_TICKS` if left out
+    pub max_ticks: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    /// False when the tick cap was hit before every process completed
+    pub finished: bool,
+    pub ticks: i32,
+    pub metrics: SimulationMetrics,
+    pub process_metrics: Vec<ProcessMetrics>,
+    pub processes: Vec<Process>,
+    pub gantt_chart: Vec<GanttBlock>,
+    pub device_gantt: Vec<DeviceGanttBlock>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessMetrics {
+    pub id: i32,
+    pub name: String,
+    pub arrival_time: i32,
+    pub burst_time: i32,
+    pub start_time: Option<i32>,
+    pub completion_time: Option<i32>,
+    /// First run minus arrival
+    pub response_time: Option<i32>,
+    pub waiting_time: i32,
+    pub turnaround_time: i32,
+    pub io_time: i32,
+    pub context_switches: i32,
+    pub migrations: i32,
+}
+
+/// Averages cover completed processes only.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationMetrics {
+    pub completed: usize,
+    pub average_response_time: f64,
+    pub average_waiting_time: f64,
+    pub average_turnaround_time: f64,
+    /// First arrival to last completion
+    pub makespan: i32,
+    /// Completed processes per tick of makespan
+    pub throughput: f64,
+    pub cpu_utilization: f64,
+    pub effective_cpu_utilization: f64,
+    pub context_switches: i32,
+    pub context_switch_time: i32,
+    pub context_switch_overhead: f64,
+    pub migrations: i32,
+    pub cores: Vec<CoreStats>,
+}
+
+/// Runs `next_tick` until every process has completed or `max_ticks` ticks
+/// have passed.
+pub fn run_to_completion(mut state: SimulationState, max_ticks: i32) -> SimulationReport {
+    let all_completed = |s: &SimulationState| s.processes.iter().all(|p| p.state == ProcessState::Completed);
+    let mut ticks = 0;
+    while !all_completed(&state) && ticks < max_ticks {
+        state = next_tick(state);
+        ticks += 1;
+    }
+
+    SimulationReport {
+        finished: all_completed(&state),
+        ticks,
+        metrics: metrics(&state),
+        process_metrics: state.processes.iter().map(process_metrics).collect(),
+        processes: state.processes,
+        gantt_chart: state.gantt_chart,
+        device_gantt: state.device_gantt,
+    }
+}
+
+fn process_metrics(p: &Process) -> ProcessMetrics {
+    ProcessMetrics {
+        id: p.id,
+        name: p.name.clone(),
+        arrival_time: p.arrival_time,
+        burst_time: p.burst_time,
+        start_time: p.start_time,
+        completion_time: p.completion_time,
+        response_time: p.start_time.map(|start| start - p.arrival_time),
+        waiting_time: p.waiting_time,
+        turnaround_time: p.turnaround_time,
+        io_time: p.io_time,
+        context_switches: p.context_switches,
+        migrations: p.migrations,
+    }
+}
+
+fn metrics(state: &SimulationState) -> SimulationMetrics {
+    let completed: Vec<&Process> = state.processes.iter()
+        .filter(|p| p.state == ProcessState::Completed)
+        .collect();
+    let average = |value: fn(&Process) -> i32| {
+        if completed.is_empty() {
+            0.0
+        } else {
+            completed.iter().map(|p| value(p) as f64).sum::<f64>() / completed.len() as f64
+        }
+    };
+
+    let first_arrival = state.processes.iter().map(|p| p.arrival_time).min().unwrap_or(0);
+    let last_completion = completed.iter().filter_map(|p| p.completion_time).max();
+    let makespan = last_completion.map_or(0, |last| last - first_arrival);
+
+    SimulationMetrics {
+        completed: completed.len(),
+        average_response_time: average(|p| p.start_time.map_or(0, |start| start - p.arrival_time)),
+        average_waiting_time: average(|p| p.waiting_time),
+        average_turnaround_time: average(|p| p.turnaround_time),
+        makespan,
+        throughput: if makespan > 0 { completed.len() as f64 / makespan as f64 } else { 0.0 },
+        cpu_utilization: state.cpu_utilization,
+        effective_cpu_utilization: state.effective_cpu_utilization,
+        context_switches: state.context_switch_count,
+        context_switch_time: state.context_switch_time_wasted,
+        context_switch_overhead: state.context_switch_overhead,
+        migrations: state.migration_count,
+        cores: state.core_stats.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::testing::{job, simulation, staggered};
+    use serde_json::json;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn runs_to_completion_with_metrics() {
+        let report = run_to_completion(simulation("FCFS", staggered(), json!({})), DEFAULT_MAX_TICKS);
+        assert!(report.finished);
+        assert_eq!(report.ticks, 6);
+
+        let responses: Vec<Option<i32>> = report.process_metrics.iter().map(|p| p.response_time).collect();
+        assert_eq!(responses, vec![Some(0), Some(2), Some(3)]);
+
+        let m = &report.metrics;
+        assert_eq!(m.completed, 3);
+        assert!(close(m.average_response_time, 5.0 / 3.0));
+        assert!(close(m.average_waiting_time, 5.0 / 3.0));
+        assert!(close(m.average_turnaround_time, 11.0 / 3.0));
+        assert_eq!(m.makespan, 6);
+        assert!(close(m.throughput, 0.5));
+        assert!(close(m.cpu_utilization, 1.0));
+    }
+
+    #[test]
+    fn stops_at_the_tick_cap() {
+        let report = run_to_completion(simulation("FCFS", staggered(), json!({})), 2);
+        assert!(!report.finished);
+        assert_eq!(report.ticks, 2);
+        assert_eq!(report.metrics.completed, 0);
+        assert_eq!(report.metrics.makespan, 0);
+        assert_eq!(report.process_metrics[2].response_time, None);
+    }
+
+    #[test]
+    fn makespan_starts_at_the_first_arrival() {
+        let report = run_to_completion(simulation("FCFS", vec![job(1, 2, 3), job(2, 2, 4)], json!({})), DEFAULT_MAX_TICKS);
+        assert_eq!(report.metrics.makespan, 4);
+        assert!(close(report.metrics.throughput, 0.5));
+    }
+}
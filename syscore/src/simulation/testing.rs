@@ -0,0 +1,103 @@
+//! Workloads and helpers shared by the simulation tests.
+
+use serde_json::{json, Value};
+use super::engine::next_tick;
+use super::types::{Process, ProcessState, SimulationState};
+
+/// A CPU-bound process with every field the engine expects, not arrived yet.
+pub fn job(id: i32, burst_time: i32, arrival_time: i32) -> Value {
+    json!({
+        "id": id,
+        "name": format!("P{}", id),
+        "burstTime": burst_time,
+        "arrivalTime": arrival_time,
+        "priority": 0,
+        "remainingTime": burst_time,
+        "color": "",
+        "state": "WAITING",
+        "startTime": null,
+        "completionTime": null,
+        "waitingTime": 0,
+        "turnaroundTime": 0
+    })
+}
+
+/// A process that computes for `cpu` ticks, waits `io` ticks on `device`,
+/// then computes for one more tick.
+pub fn io_job(id: i32, cpu: i32, device: i32, io: i32) -> Value {
+    with(job(id, 0, 0), json!({
+        "bursts": [
+            { "kind": "CPU", "duration": cpu },
+            { "kind": "IO", "device": device, "duration": io },
+            { "kind": "CPU", "duration": 1 }
+        ]
+    }))
+}
+
+/// `value` with the fields of `overrides` set on top.
+pub fn with(mut value: Value, overrides: Value) -> Value {
+    for (key, field) in overrides.as_object().into_iter().flatten() {
+        value[key] = field.clone();
+    }
+    value
+}
+
+/// P1 needs 3 ticks from 0, P2 2 ticks from 1 and P3 1 tick from 2.
+pub fn staggered() -> Vec<Value> {
+    vec![job(1, 3, 0), job(2, 2, 1), job(3, 1, 2)]
+}
+
+/// A simulation at time 0; `settings` overrides any top-level field.
+pub fn simulation(algorithm: &str, processes: Vec<Value>, settings: Value) -> SimulationState {
+    let state = json!({
+        "currentTime": 0,
+        "processes": processes,
+        "readyQueue": [],
+        "runningProcessId": null,
+        "completedProcessIds": [],
+        "ganttChart": [],
+        "algorithm": algorithm,
+        "timeQuantum": 2,
+        "quantumRemaining": 0,
+        "isPlaying": false,
+        "speed": 500
+    });
+    serde_json::from_value(with(state, settings)).expect("valid simulation state")
+}
+
+pub fn ticks(mut state: SimulationState, count: i32) -> SimulationState {
+    for _ in 0..count {
+        state = next_tick(state);
+    }
+    state
+}
+
+/// Ticks until every process has completed.
+pub fn run(mut state: SimulationState) -> SimulationState {
+    while state.processes.iter().any(|p| p.state != ProcessState::Completed) {
+        assert!(state.current_time < 1_000, "simulation did not finish");
+        state = next_tick(state);
+    }
+    state
+}
+
+/// What ran on core 0 each tick, 0 when nothing did.
+pub fn timeline(state: &SimulationState) -> Vec<i32> {
+    core_timeline(state, 0)
+}
+
+pub fn core_timeline(state: &SimulationState, core: i32) -> Vec<i32> {
+    state.gantt_chart.iter()
+        .filter(|b| b.core_id == core)
+        .flat_map(|b| (b.start_time..b.end_time).map(|_| b.process_id.unwrap_or(0)))
+        .collect()
+}
+
+/// A process that is already waiting in a run queue.
+pub fn ready(id: i32, burst_time: i32) -> Value {
+    with(job(id, burst_time, 0), json!({ "state": "READY" }))
+}
+
+pub fn process(state: &SimulationState, id: i32) -> &Process {
+    state.processes.iter().find(|p| p.id == id).expect("known process")
+}
@@ -1,64 +1,539 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ProcessState {
     Ready,
     Running,
     Completed,
+    /// Not arrived yet
+    #[default]
     Waiting,
+    /// Queued on or being served by a device
+    Blocked,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BurstKind {
+    Cpu,
+    Io,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Burst {
+    pub kind: BurstKind,
+    /// Ticks of CPU, or of I/O once the device picks the request up.
+    /// An I/O burst without one takes the device's `serviceTime`.
+    #[serde(default)]
+    pub duration: i32,
+    /// Device id, for I/O bursts
+    #[serde(default)]
+    pub device: i32,
+}
+
+impl Burst {
+    pub fn is_io(&self) -> bool {
+        self.kind == BurstKind::Io
+    }
+}
+
+/// An I/O device serving one request at a time, first come first served.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    pub id: i32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_service_time")]
+    pub service_time: i32,
+    /// Blocked processes waiting for the device
+    #[serde(default)]
+    pub queue: Vec<i32>,
+    /// Process being served
+    #[serde(default)]
+    pub current_process_id: Option<i32>,
+    #[serde(default)]
+    pub remaining: i32,
+    #[serde(default)]
+    pub busy_time: i32,
+}
+
+impl Device {
+    pub fn new(id: i32) -> Self {
+        Self {
+            id,
+            name: format!("Device {}", id),
+            service_time: default_service_time(),
+            queue: Vec::new(),
+            current_process_id: None,
+            remaining: 0,
+            busy_time: 0,
+        }
+    }
+}
+
+fn default_service_time() -> i32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceGanttBlock {
+    pub device_id: i32,
+    pub process_id: i32,
+    pub start_time: i32,
+    pub end_time: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Process {
     pub id: i32,
+    #[serde(default)]
     pub name: String,
     pub burst_time: i32,
+    #[serde(default)]
     pub arrival_time: i32,
+    #[serde(default)]
     pub priority: i32,
+    /// `priority` improved by aging while the process waits, reset when it
+    /// leaves the ready queue. Lower is more urgent, like `priority`.
+    #[serde(default)]
+    pub effective_priority: Option<i32>,
+    /// Tick the process last joined a ready queue, what aging counts from
+    #[serde(default)]
+    pub ready_since: Option<i32>,
+    /// Taken from `burst_time` on arrival when left out
+    #[serde(default)]
     pub remaining_time: i32,
+    #[serde(default)]
     pub color: String,
+    #[serde(default)]
     pub state: ProcessState,
+    /// MLFQ level, 0 is the highest priority
+    #[serde(default)]
+    pub queue_level: i32,
+    /// Alternating CPU and I/O bursts. Empty means one CPU burst of
+    /// `burst_time`; otherwise `burst_time` is the CPU total and
+    /// `remaining_time` what is left of the current CPU burst.
+    #[serde(default)]
+    pub bursts: Vec<Burst>,
+    /// Index of the current burst in `bursts`
+    #[serde(default)]
+    pub burst_index: i32,
+    /// Cores the process may run on; empty means any
+    #[serde(default)]
+    pub affinity: Vec<i32>,
+    /// Core running the process
+    #[serde(default)]
+    pub core_id: Option<i32>,
+    /// Core the process last ran on, to tell migrations apart
+    #[serde(default)]
+    pub last_core: Option<i32>,
 
     // Stats
+    #[serde(default)]
     pub start_time: Option<i32>,
+    #[serde(default)]
     pub completion_time: Option<i32>,
+    #[serde(default)]
     pub waiting_time: i32,
+    #[serde(default)]
     pub turnaround_time: i32,
+    /// Ticks spent blocked, queued for a device or being served
+    #[serde(default)]
+    pub io_time: i32,
+    #[serde(default)]
+    pub migrations: i32,
+    /// Ticks spent warming up on a new core after migrating
+    #[serde(default)]
+    pub migration_time: i32,
+    /// Context switches that put this process on a core
+    #[serde(default)]
+    pub context_switches: i32,
 }
 
+impl Process {
+    /// A process that hasn't arrived yet, with no stats.
+    pub fn new(id: i32, name: String, burst_time: i32, arrival_time: i32, priority: i32, color: String) -> Self {
+        Self {
+            id,
+            name,
+            burst_time,
+            arrival_time,
+            priority,
+            effective_priority: Some(priority),
+            ready_since: None,
+            remaining_time: burst_time,
+            color,
+            state: ProcessState::Waiting,
+            queue_level: 0,
+            bursts: Vec::new(),
+            burst_index: 0,
+            affinity: Vec::new(),
+            core_id: None,
+            last_core: None,
+            start_time: None,
+            completion_time: None,
+            waiting_time: 0,
+            turnaround_time: 0,
+            io_time: 0,
+            migrations: 0,
+            migration_time: 0,
+            context_switches: 0,
+        }
+    }
+
+    pub fn effective_priority(&self) -> i32 {
+        self.effective_priority.unwrap_or(self.priority)
+    }
+
+    /// Whether `core` is in the affinity set. An affinity naming none of
+    /// the `num_cores` cores doesn't restrict the process.
+    pub fn runs_on(&self, core: i32, num_cores: i32) -> bool {
+        self.affinity.is_empty()
+            || self.affinity.contains(&core)
+            || !self.affinity.iter().any(|c| (0..num_cores).contains(c))
+    }
+
+    pub fn current_burst(&self) -> Option<&Burst> {
+        self.bursts.get(self.burst_index.max(0) as usize)
+    }
+}
+
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum AlgorithmType {
     FCFS,
     SJF,
     SRTF,
     RR,
+    /// Non-preemptive priority
     PRIORITY,
+    /// Preemptive priority
+    PRIORITY_P,
+    MLFQ,
 }
 
+impl AlgorithmType {
+    /// Algorithms that take the CPU away when a time slice runs out
+    pub fn is_time_sliced(&self) -> bool {
+        matches!(self, AlgorithmType::RR | AlgorithmType::MLFQ)
+    }
+
+    pub fn is_priority(&self) -> bool {
+        matches!(self, AlgorithmType::PRIORITY | AlgorithmType::PRIORITY_P)
+    }
+}
+
+/// `process_id` of a Gantt block where the core was switching contexts;
+/// `None` is idle.
+pub const CONTEXT_SWITCH_PROCESS_ID: i32 = -1;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GanttBlock {
     pub process_id: Option<i32>,
     pub start_time: i32,
     pub end_time: i32,
+    #[serde(default)]
+    pub core_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QueueMode {
+    /// One ready queue shared by every core
+    #[default]
+    Global,
+    /// A run queue per core, kept level by load balancing
+    PerCore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreStats {
+    pub busy_time: i32,
+    pub idle_time: i32,
+    #[serde(default)]
+    pub switch_time: i32,
+    pub utilization: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SimulationState {
+    // Everything but `processes` and `algorithm` may be left out when
+    // starting a simulation
+    #[serde(default)]
     pub current_time: i32,
     pub processes: Vec<Process>,
+    #[serde(default)]
     pub ready_queue: Vec<i32>, // Process IDs
-    pub running_process_id: Option<i32>,
+    #[serde(default)]
+    pub running_process_id: Option<i32>, // Core 0, see `running_process_ids`
+    #[serde(default)]
     pub completed_process_ids: Vec<i32>,
+    #[serde(default)]
     pub gantt_chart: Vec<GanttBlock>,
 
     // Control State
     pub algorithm: AlgorithmType,
+    #[serde(default = "default_time_quantum")]
     pub time_quantum: i32,
-    pub quantum_remaining: i32, // For RR
+    /// Time slice left per core. A single number is taken as core 0's.
+    #[serde(default, deserialize_with = "one_or_per_core")]
+    pub quantum_remaining: Vec<i32>,
+    #[serde(default)]
     pub is_playing: bool,
+    #[serde(default = "default_speed")]
     pub speed: i32,
+
+    // Priority aging: a ready process gains one priority level every
+    // `priority_aging_interval` ticks it has waited since arriving
+    #[serde(default)]
+    pub priority_aging_enabled: bool,
+    #[serde(default = "default_priority_aging_interval")]
+    pub priority_aging_interval: i32,
+
+    // MLFQ. The ready queue and each process's `queue_level` are what the
+    // scheduler goes by; `mlfq_queues` is rebuilt from them every tick.
+    #[serde(default)]
+    pub mlfq_queues: Vec<Vec<i32>>,
+    #[serde(default = "default_mlfq_quantums")]
+    pub mlfq_quantums: Vec<i32>,
+    #[serde(default = "default_mlfq_num_queues")]
+    pub mlfq_num_queues: i32,
+    /// Level of the running process
+    #[serde(default)]
+    pub mlfq_current_level: Vec<i32>,
+    /// Every this many ticks all processes go back to level 0 (0 = never)
+    #[serde(default)]
+    pub mlfq_boost_interval: i32,
+
+    // I/O. Devices named by a burst but missing here are added with a
+    // service time of 1.
+    #[serde(default)]
+    pub devices: Vec<Device>,
+    /// Busy periods per device
+    #[serde(default)]
+    pub device_gantt: Vec<DeviceGanttBlock>,
+
+    // CPU accounting. Idle ticks only count while some process hasn't
+    // completed; `cpu_idle_blocked_time` is the part of them where a process
+    // was blocked on I/O.
+    #[serde(default)]
+    pub cpu_busy_time: i32,
+    #[serde(default)]
+    pub cpu_idle_time: i32,
+    #[serde(default)]
+    pub cpu_idle_blocked_time: i32,
+    /// Time not idle (busy or switching) over busy, switching and idle time
+    #[serde(default)]
+    pub cpu_utilization: f64,
+    /// Like `cpu_utilization`, but only counting time spent running processes
+    #[serde(default)]
+    pub effective_cpu_utilization: f64,
+
+    // Context switches. Going from one process to another costs
+    // `context_switch_cost` ticks, and starting any new process on a core
+    // another `dispatch_latency`; the core runs nothing meanwhile.
+    #[serde(default)]
+    pub context_switch_cost: i32,
+    #[serde(default)]
+    pub dispatch_latency: i32,
+    #[serde(default)]
+    pub context_switch_count: i32,
+    /// Ticks spent switching and dispatching, all cores together
+    #[serde(default)]
+    pub context_switch_time_wasted: i32,
+    /// Switching ticks left per core
+    #[serde(default)]
+    pub context_switch_cooldown: Vec<i32>,
+    /// Switching time over switching plus busy time
+    #[serde(default)]
+    pub context_switch_overhead: f64,
+
+    // Multiprocessor. The single-core fields above mirror core 0, and the
+    // CPU accounting adds up all cores. With `PER_CORE` queues,
+    // `ready_queue` is every core queue in turn.
+    #[serde(default = "default_num_cores")]
+    pub num_cores: i32,
+    #[serde(default)]
+    pub queue_mode: QueueMode,
+    #[serde(default)]
+    pub running_process_ids: Vec<Option<i32>>,
+    #[serde(default)]
+    pub core_queues: Vec<Vec<i32>>,
+    /// Ticks of migration warm-up left per core
+    #[serde(default)]
+    pub core_stall: Vec<i32>,
+    #[serde(default)]
+    pub core_stats: Vec<CoreStats>,
+    /// Ticks a process needs on a new core before it makes progress again
+    #[serde(default)]
+    pub migration_cost: i32,
+    #[serde(default)]
+    pub migration_count: i32,
+    /// `PER_CORE`: every this many ticks, push work from the busiest core
+    /// to the least busy one (0 = never)
+    #[serde(default)]
+    pub balance_interval: i32,
+    /// `PER_CORE`: an idle core pulls work from the busiest one
+    #[serde(default)]
+    pub idle_pull: bool,
+}
+
+fn one_or_per_core<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrPerCore {
+        One(i32),
+        PerCore(Vec<i32>),
+    }
+    Ok(match OneOrPerCore::deserialize(deserializer)? {
+        OneOrPerCore::One(value) => vec![value],
+        OneOrPerCore::PerCore(values) => values,
+    })
+}
+
+fn default_num_cores() -> i32 {
+    1
+}
+
+fn default_time_quantum() -> i32 {
+    2
+}
+
+fn default_speed() -> i32 {
+    500
+}
+
+fn default_priority_aging_interval() -> i32 {
+    5
+}
+
+fn default_mlfq_quantums() -> Vec<i32> {
+    vec![2, 4, 8]
+}
+
+fn default_mlfq_num_queues() -> i32 {
+    3
+}
+
+/// Most MLFQ levels a client may ask for; every tick allocates per level.
+pub const MAX_MLFQ_QUEUES: i32 = 16;
+
+/// Most cores a simulation may have; every tick allocates per core.
+pub const MAX_CORES: i32 = 256;
+
+impl SimulationState {
+    /// Rejects settings a tick would have to allocate for without bound.
+    /// Every entry point that takes a state from a client checks it first.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mlfq_num_queues > MAX_MLFQ_QUEUES {
+            return Err(format!("mlfqNumQueues must be at most {}", MAX_MLFQ_QUEUES));
+        }
+        if self.num_cores > MAX_CORES {
+            return Err(format!("numCores must be at most {}", MAX_CORES));
+        }
+        Ok(())
+    }
+
+    /// The MLFQ time slice at `level`; levels past the configured quanta use the last one.
+    pub fn mlfq_quantum(&self, level: i32) -> i32 {
+        let quantum = self.mlfq_quantums.get(level.max(0) as usize)
+            .or(self.mlfq_quantums.last())
+            .copied()
+            .unwrap_or(self.time_quantum);
+        quantum.max(1)
+    }
+
+    pub fn mlfq_lowest_level(&self) -> i32 {
+        self.mlfq_num_queues.max(1) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::engine::next_tick;
+    use serde_json::json;
+
+    /// A state as the web app sends it (apps/web/src/core/types.ts)
+    fn frontend_state() -> serde_json::Value {
+        let process = |id: i32, burst: i32| json!({
+            "id": id, "name": format!("P{}", id), "burstTime": burst, "arrivalTime": 0,
+            "priority": 1, "effectivePriority": 1, "remainingTime": burst, "color": "#fff",
+            "state": "WAITING", "queueLevel": 0, "coreId": null, "startTime": null,
+            "completionTime": null, "waitingTime": 0, "turnaroundTime": 0
+        });
+        json!({
+            "currentTime": 0,
+            "processes": [process(1, 3), process(2, 2)],
+            "readyQueue": [],
+            "runningProcessIds": [null, null],
+            "completedProcessIds": [],
+            "ganttChart": [],
+            "algorithm": "MLFQ",
+            "timeQuantum": 2,
+            "quantumRemaining": [0, 0],
+            "isPlaying": false,
+            "speed": 500,
+            "contextSwitchCost": 0,
+            "contextSwitchCount": 0,
+            "contextSwitchTimeWasted": 0,
+            "contextSwitchCooldown": [0, 0],
+            "priorityAgingEnabled": false,
+            "priorityAgingInterval": 5,
+            "numCores": 2,
+            "mlfqQueues": [[], [], []],
+            "mlfqQuantums": [2, 4, 8],
+            "mlfqNumQueues": 3,
+            "mlfqCurrentLevel": [0, 0]
+        })
+    }
+
+    #[test]
+    fn frontend_state_round_trips() {
+        let state: SimulationState = serde_json::from_value(frontend_state()).unwrap();
+        assert_eq!(state.quantum_remaining, vec![0, 0]);
+
+        let next = serde_json::to_value(next_tick(state)).unwrap();
+        assert_eq!(next["quantumRemaining"], json!([1, 1]));
+        assert!(next.get("coreQuantumRemaining").is_none());
+
+        let again: SimulationState = serde_json::from_value(next).unwrap();
+        assert_eq!(again.quantum_remaining, vec![1, 1]);
+        assert_eq!(again.running_process_ids, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn single_quantum_is_core_zero() {
+        let mut state = frontend_state();
+        state["quantumRemaining"] = json!(1);
+        let state: SimulationState = serde_json::from_value(state).unwrap();
+        assert_eq!(state.quantum_remaining, vec![1]);
+    }
+
+    #[test]
+    fn validate_caps_mlfq_levels() {
+        let mut state: SimulationState = serde_json::from_value(frontend_state()).unwrap();
+        assert!(state.validate().is_ok());
+
+        state.mlfq_num_queues = MAX_MLFQ_QUEUES + 1;
+        assert!(state.validate().is_err());
+    }
+
+    #[test]
+    fn validate_caps_cores() {
+        let mut state: SimulationState = serde_json::from_value(frontend_state()).unwrap();
+        state.num_cores = MAX_CORES;
+        assert!(state.validate().is_ok());
+
+        state.num_cores = MAX_CORES + 1;
+        assert!(state.validate().is_err());
+    }
 }